@@ -12,7 +12,7 @@ use windows::{
     Win32::UI::WindowsAndMessaging::{MessageBoxA, MB_ICONWARNING, MB_OK},
 };
 
-use crate::services::AppConfiguration;
+use crate::services::{voice_commands::VoiceCommands, AppConfiguration};
 
 mod services;
 
@@ -80,6 +80,7 @@ fn main() {
         .invoke_handler(tauri::generate_handler![get_port, get_native_features, app_close])
         .plugin(tauri_plugin_window_state::Builder::default().build())
         .manage(AppConfiguration { port: args.port })
+        .manage(VoiceCommands::default())
         .plugin(services::osc::init())
         .plugin(services::web::init())
         .plugin(services::audio::init())
@@ -88,6 +89,7 @@ fn main() {
         .plugin(services::keyboard::init())
         .plugin(services::uwu::init())
         .plugin(services::whisper::init())
+        .plugin(services::voice_commands::init())
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -3,6 +3,7 @@ pub mod keyboard;
 pub mod osc;
 pub mod uberduck_tts;
 pub mod uwu;
+pub mod voice_commands;
 pub mod web;
 pub mod whisper;
 pub mod windows_tts;
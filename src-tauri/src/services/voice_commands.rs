@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tauri::{
+    command,
+    plugin::{Builder, TauriPlugin},
+    Runtime, State,
+};
+
+const DEFAULT_THRESHOLD: f32 = 0.8;
+/// Extra words a match may span, recognizers split words up ("mute t t s").
+const WINDOW_SLACK: usize = 2;
+/// `TextEventType.final` on the JS side.
+const TEXT_EVENT_FINAL: u64 = 0;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VoiceCommand {
+    pub id: String,
+    pub phrase: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct VoiceCommandConfig {
+    pub commands: Vec<VoiceCommand>,
+    /// Similarity from 0 to 1 a phrase needs to count as said.
+    pub threshold: f32,
+    /// Take matched phrases out of the text that goes on to captions.
+    pub suppress: bool,
+}
+
+impl Default for VoiceCommandConfig {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            threshold: DEFAULT_THRESHOLD,
+            suppress: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandSource {
+    Whisper,
+    Pubsub,
+}
+
+/// Payload of `voice_command`.
+#[derive(Clone, Debug, Serialize)]
+pub struct VoiceCommandPayload {
+    pub id: String,
+    pub phrase: String,
+    /// Words of the transcript the phrase matched.
+    pub heard: String,
+    pub score: f32,
+    pub source: CommandSource,
+    /// Whisper session, None for pubsub.
+    pub session: Option<String>,
+}
+
+/// Commands found in one final result.
+pub struct Matched {
+    /// In the order they were said.
+    pub commands: Vec<VoiceCommandPayload>,
+    /// Text for captions, without the phrases when suppressing. Empty when nothing else was said.
+    pub text: String,
+}
+
+struct Word {
+    /// Index into the whitespace split of the text.
+    index: usize,
+    normalized: String,
+}
+
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + (ca != cb) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// 1 for the same letters, spaces and punctuation do not count.
+fn similarity(a: &[char], b: &[char]) -> f32 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(a, b) as f32 / longest as f32
+}
+
+struct Candidate {
+    command: usize,
+    /// Range of `Word`s.
+    start: usize,
+    end: usize,
+    score: f32,
+}
+
+impl VoiceCommandConfig {
+    /// Best non-overlapping matches, each command at most once.
+    fn find(&self, words: &[Word]) -> Vec<Candidate> {
+        let mut candidates = Vec::new();
+        for (command_index, command) in self.commands.iter().enumerate() {
+            let phrase_words: Vec<String> = command
+                .phrase
+                .split_whitespace()
+                .map(normalize)
+                .filter(|w| !w.is_empty())
+                .collect();
+            if phrase_words.is_empty() {
+                continue;
+            }
+            let phrase: Vec<char> = phrase_words.concat().chars().collect();
+            let min_len = phrase_words.len().saturating_sub(1).max(1);
+            let max_len = phrase_words.len() + WINDOW_SLACK;
+            for start in 0..words.len() {
+                for end in (start + min_len)..=(start + max_len).min(words.len()) {
+                    let heard: Vec<char> = words[start..end]
+                        .iter()
+                        .flat_map(|w| w.normalized.chars())
+                        .collect();
+                    let score = similarity(&phrase, &heard);
+                    if score >= self.threshold {
+                        candidates.push(Candidate {
+                            command: command_index,
+                            start,
+                            end,
+                            score,
+                        });
+                    }
+                }
+            }
+        }
+        // highest score first, the earlier and shorter window on ties
+        candidates.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.start.cmp(&b.start))
+                .then(a.end.cmp(&b.end))
+        });
+        let mut picked: Vec<Candidate> = Vec::new();
+        for candidate in candidates {
+            let taken = picked
+                .iter()
+                .any(|p| p.command == candidate.command || (candidate.start < p.end && p.start < candidate.end));
+            if !taken {
+                picked.push(candidate);
+            }
+        }
+        picked.sort_by_key(|p| p.start);
+        picked
+    }
+
+    /// Looks for the commands in a final result, None when there are none.
+    pub fn process(&self, text: &str, source: CommandSource, session: Option<&str>) -> Option<Matched> {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let words: Vec<Word> = tokens
+            .iter()
+            .enumerate()
+            .map(|(index, token)| Word {
+                index,
+                normalized: normalize(token),
+            })
+            .filter(|w| !w.normalized.is_empty())
+            .collect();
+        let found = self.find(&words);
+        if found.is_empty() {
+            return None;
+        }
+
+        let mut removed = vec![false; tokens.len()];
+        let commands = found
+            .iter()
+            .map(|candidate| {
+                let (first, last) = (words[candidate.start].index, words[candidate.end - 1].index);
+                removed[first..=last].iter_mut().for_each(|r| *r = true);
+                let command = &self.commands[candidate.command];
+                VoiceCommandPayload {
+                    id: command.id.clone(),
+                    phrase: command.phrase.clone(),
+                    heard: tokens[first..=last].join(" "),
+                    score: candidate.score,
+                    source,
+                    session: session.map(str::to_string),
+                }
+            })
+            .collect();
+
+        let text = if self.suppress {
+            let rest: Vec<&str> = tokens
+                .iter()
+                .zip(&removed)
+                .filter(|(_, removed)| !**removed)
+                .map(|(token, _)| *token)
+                .collect();
+            let rest = rest.join(" ");
+            // a lone "." or "," is not worth a caption
+            if rest.chars().any(char::is_alphanumeric) {
+                rest
+            } else {
+                String::new()
+            }
+        } else {
+            text.to_string()
+        };
+        Some(Matched { commands, text })
+    }
+}
+
+/// Commands set from the UI, shared between the Whisper pipeline and pubsub.
+#[derive(Clone, Default)]
+pub struct VoiceCommands(Arc<Mutex<VoiceCommandConfig>>);
+
+impl VoiceCommands {
+    pub fn set(&self, config: VoiceCommandConfig) {
+        *self.0.lock().unwrap() = config;
+    }
+
+    pub fn process(&self, text: &str, source: CommandSource, session: Option<&str>) -> Option<Matched> {
+        let config = self.0.lock().unwrap();
+        if config.commands.is_empty() {
+            return None;
+        }
+        config.process(text, source, session)
+    }
+
+    /// Runs the commands over a `text.stt` final from a pubsub peer.
+    /// Returns the message to hand on to the app, None when suppression left nothing to show.
+    pub fn process_pubsub(&self, message: String) -> (Vec<VoiceCommandPayload>, Option<String>) {
+        let Ok(mut event) = serde_json::from_str::<Value>(&message) else {
+            return (Vec::new(), Some(message));
+        };
+        if event["topic"] != "text.stt" || event["data"]["type"].as_u64() != Some(TEXT_EVENT_FINAL) {
+            return (Vec::new(), Some(message));
+        }
+        let Some(text) = event["data"]["value"].as_str() else {
+            return (Vec::new(), Some(message));
+        };
+        let Some(matched) = self.process(text, CommandSource::Pubsub, None) else {
+            return (Vec::new(), Some(message));
+        };
+        if matched.text.is_empty() {
+            return (matched.commands, None);
+        }
+        if matched.text == text {
+            return (matched.commands, Some(message));
+        }
+        event["data"]["value"] = Value::String(matched.text);
+        (matched.commands, Some(event.to_string()))
+    }
+}
+
+#[command]
+fn set_voice_commands(config: VoiceCommandConfig, commands: State<'_, VoiceCommands>) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&config.threshold) {
+        return Err("Threshold must be between 0 and 1".to_string());
+    }
+    commands.set(config);
+    Ok(())
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new("voice_commands")
+        .invoke_handler(tauri::generate_handler![set_voice_commands])
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(suppress: bool) -> VoiceCommands {
+        let commands = VoiceCommands::default();
+        commands.set(VoiceCommandConfig {
+            commands: [
+                ("captions_clear", "captions clear"),
+                ("scene_gaming", "switch scene gaming"),
+                ("mute_tts", "mute tts"),
+            ]
+            .into_iter()
+            .map(|(id, phrase)| VoiceCommand {
+                id: id.to_string(),
+                phrase: phrase.to_string(),
+            })
+            .collect(),
+            suppress,
+            ..VoiceCommandConfig::default()
+        });
+        commands
+    }
+
+    fn ids(matched: &Matched) -> Vec<&str> {
+        matched.commands.iter().map(|c| c.id.as_str()).collect()
+    }
+
+    #[test]
+    fn matches_phrases_the_way_whisper_writes_them() {
+        let commands = commands(false);
+        let matched = commands
+            .process("Caption's clear.", CommandSource::Whisper, Some("default"))
+            .unwrap();
+        assert_eq!(ids(&matched), ["captions_clear"]);
+        assert_eq!(matched.commands[0].heard, "Caption's clear.");
+        assert_eq!(matched.commands[0].session.as_deref(), Some("default"));
+        assert_eq!(matched.text, "Caption's clear.");
+
+        let matched = commands
+            .process("Okay, mute T.T.S. and switch seen gaming", CommandSource::Whisper, None)
+            .unwrap();
+        assert_eq!(ids(&matched), ["mute_tts", "scene_gaming"]);
+        assert!(commands
+            .process("mute T T S", CommandSource::Whisper, None)
+            .is_some());
+    }
+
+    #[test]
+    fn ignores_speech_that_only_sounds_close() {
+        let commands = commands(false);
+        assert!(commands
+            .process("mute this one please", CommandSource::Whisper, None)
+            .is_none());
+        assert!(commands
+            .process("the captions are clearly wrong", CommandSource::Whisper, None)
+            .is_none());
+        assert!(commands
+            .process("switch to the main scene", CommandSource::Whisper, None)
+            .is_none());
+        assert!(VoiceCommands::default()
+            .process("captions clear", CommandSource::Whisper, None)
+            .is_none());
+    }
+
+    #[test]
+    fn suppresses_only_the_phrase() {
+        let commands = commands(true);
+        let matched = commands
+            .process("Hello chat. Switch scene gaming. See you there!", CommandSource::Whisper, None)
+            .unwrap();
+        assert_eq!(matched.text, "Hello chat. See you there!");
+        let matched = commands
+            .process("Mute TTS.", CommandSource::Whisper, None)
+            .unwrap();
+        assert_eq!(matched.text, "");
+    }
+
+    #[test]
+    fn rewrites_pubsub_finals_and_leaves_the_rest() {
+        let commands = commands(true);
+        let interim = r#"{"topic":"text.stt","data":{"type":1,"value":"mute tts"}}"#.to_string();
+        let (found, message) = commands.process_pubsub(interim.clone());
+        assert!(found.is_empty());
+        assert_eq!(message, Some(interim));
+
+        let other = r#"{"topic":"text.translation","data":{"type":0,"value":"mute tts"}}"#.to_string();
+        assert!(commands.process_pubsub(other).0.is_empty());
+
+        let final_only = r#"{"topic":"text.stt","data":{"type":0,"value":"mute tts"}}"#.to_string();
+        let (found, message) = commands.process_pubsub(final_only);
+        assert_eq!(found[0].source, CommandSource::Pubsub);
+        assert!(message.is_none());
+
+        let mixed = r#"{"topic":"text.stt","data":{"type":0,"value":"captions clear thanks for the raid"}}"#.to_string();
+        let (found, message) = commands.process_pubsub(mixed);
+        assert_eq!(found.len(), 1);
+        let event: Value = serde_json::from_str(&message.unwrap()).unwrap();
+        assert_eq!(event["data"]["value"], "thanks for the raid");
+    }
+}
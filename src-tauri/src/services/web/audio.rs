@@ -0,0 +1,263 @@
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::thread;
+use tauri::{AppHandle, Manager, Runtime};
+use tokio::sync::mpsc;
+use warp::{
+    filters::BoxedFilter,
+    ws::{Message, WebSocket, Ws},
+    Filter, Reply,
+};
+
+use crate::services::whisper::{RemoteSource, WhisperState};
+
+// Remote microphone protocol:
+// 1. client sends a text `hello` declaring the stream format and the whisper session to feed,
+//    server answers `ready` or `error`
+// 2. client sends binary frames: u32 LE sequence number followed by interleaved samples
+// 3. frames arriving late or twice are dropped, gaps are counted and reported on close
+
+const MAX_CHANNELS: u16 = 8;
+/// Frames waiting for the feeder thread, the socket stops being read once it is full.
+const FEED_CAPACITY: usize = 64;
+
+#[derive(Deserialize)]
+pub struct AudioQueryData {
+    id: String,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SampleFormat {
+    F32le,
+    S16le,
+}
+
+impl SampleFormat {
+    fn bytes_per_sample(&self) -> usize {
+        match self {
+            SampleFormat::F32le => 4,
+            SampleFormat::S16le => 2,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Vec<f32> {
+        match self {
+            SampleFormat::F32le => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            SampleFormat::S16le => bytes
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    #[serde(rename_all = "camelCase")]
+    Hello {
+        sample_rate: u32,
+        channels: u16,
+        format: SampleFormat,
+        /// Whisper session to feed, the default session when left out.
+        session: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerMessage {
+    Ready,
+    Error { error: String },
+}
+
+impl From<ServerMessage> for Message {
+    fn from(message: ServerMessage) -> Self {
+        Message::text(serde_json::to_string(&message).unwrap_or_default())
+    }
+}
+
+/// Payload of `whisper:remote_session`, sent when a remote microphone connects and disconnects.
+#[derive(Clone, Serialize)]
+struct RemoteSessionPayload {
+    id: String,
+    session: String,
+    connected: bool,
+    sample_rate: u32,
+    channels: u16,
+    format: SampleFormat,
+    received_frames: u64,
+    lost_frames: u64,
+    dropped_frames: u64,
+}
+
+/// Runs the source on its own thread, preprocessing, the VAD and a blocked queue must not hold up the runtime.
+/// The thread ends once the sender is dropped.
+fn spawn_feeder<R: Runtime>(app: AppHandle<R>, mut source: RemoteSource) -> mpsc::Sender<Vec<f32>> {
+    let (tx, mut rx) = mpsc::channel::<Vec<f32>>(FEED_CAPACITY);
+    thread::spawn(move || {
+        while let Some(samples) = rx.blocking_recv() {
+            if let Some(state) = app.try_state::<WhisperState>() {
+                source.push(&state, &app, &samples);
+            }
+        }
+    });
+    tx
+}
+
+struct Session {
+    format: SampleFormat,
+    channels: u16,
+    feed: mpsc::Sender<Vec<f32>>,
+    next_sequence: Option<u32>,
+    payload: RemoteSessionPayload,
+}
+
+impl Session {
+    fn new<R: Runtime>(
+        app: &AppHandle<R>,
+        id: &str,
+        session: Option<String>,
+        sample_rate: u32,
+        channels: u16,
+        format: SampleFormat,
+    ) -> Result<Self, String> {
+        if !(8000..=192000).contains(&sample_rate) {
+            return Err(format!("Unsupported sample rate: {}", sample_rate));
+        }
+        if channels == 0 || channels > MAX_CHANNELS {
+            return Err(format!("Unsupported channel count: {}", channels));
+        }
+        let source = RemoteSource::new(session, sample_rate, channels);
+        Ok(Self {
+            format,
+            channels,
+            next_sequence: None,
+            payload: RemoteSessionPayload {
+                id: id.to_string(),
+                session: source.session().to_string(),
+                connected: true,
+                sample_rate,
+                channels,
+                format,
+                received_frames: 0,
+                lost_frames: 0,
+                dropped_frames: 0,
+            },
+            feed: spawn_feeder(app.clone(), source),
+        })
+    }
+
+    /// Returns the samples of an in-order frame, None for late or duplicate frames.
+    fn frame(&mut self, bytes: &[u8]) -> Result<Option<Vec<f32>>, String> {
+        if bytes.len() < 4 {
+            return Err("Frame is missing its sequence number".to_string());
+        }
+        let (header, body) = bytes.split_at(4);
+        let frame_size = self.format.bytes_per_sample() * self.channels as usize;
+        if body.len() % frame_size != 0 {
+            return Err(format!("Frame length {} is not a whole number of {} byte frames", body.len(), frame_size));
+        }
+
+        let sequence = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        if let Some(expected) = self.next_sequence {
+            // wrapping distance, so a sequence counter overflowing after a long session keeps working
+            let ahead = sequence.wrapping_sub(expected);
+            if ahead > u32::MAX / 2 {
+                self.payload.dropped_frames += 1;
+                return Ok(None);
+            }
+            self.payload.lost_frames += ahead as u64;
+        }
+        self.next_sequence = Some(sequence.wrapping_add(1));
+        self.payload.received_frames += 1;
+        Ok(Some(self.format.decode(body)))
+    }
+}
+
+fn handshake<R: Runtime>(app: &AppHandle<R>, id: &str, msg: &str) -> Result<Session, String> {
+    match serde_json::from_str::<ClientMessage>(msg).map_err(|e| e.to_string())? {
+        ClientMessage::Hello {
+            sample_rate,
+            channels,
+            format,
+            session,
+        } => Session::new(app, id, session, sample_rate, channels, format),
+    }
+}
+
+pub fn path<R: Runtime>(app: AppHandle<R>) -> BoxedFilter<(impl Reply,)> {
+    let app = warp::any().map(move || app.clone());
+    let t = warp::path("audio")
+        .and(warp::ws())
+        .and(app)
+        .and(warp::query::<AudioQueryData>())
+        .map(|ws: Ws, app, q| ws.on_upgrade(move |socket| audio_handler(socket, app, q)))
+        .boxed();
+    t
+}
+
+pub async fn audio_handler<R: Runtime>(ws: WebSocket, app: AppHandle<R>, query: AudioQueryData) {
+    eprintln!("[Audio] New remote microphone: {}", query.id);
+    let (mut peer_tx, mut peer_rx) = ws.split();
+    let mut session: Option<Session> = None;
+
+    while let Some(result) = peer_rx.next().await {
+        let Ok(msg) = result else {
+            break;
+        };
+
+        if msg.is_text() {
+            let Ok(msg_str) = msg.to_str() else { break };
+            let reply = match handshake(&app, &query.id, msg_str) {
+                Ok(new_session) => {
+                    app.emit_all("whisper:remote_session", new_session.payload.clone())
+                        .ok();
+                    session = Some(new_session);
+                    ServerMessage::Ready
+                }
+                Err(error) => ServerMessage::Error { error },
+            };
+            if peer_tx.send(reply.into()).await.is_err() {
+                break;
+            }
+            continue;
+        }
+
+        if msg.is_binary() {
+            let Some(active) = session.as_mut() else {
+                let error = "Send a hello message before audio".to_string();
+                peer_tx
+                    .send(ServerMessage::Error { error }.into())
+                    .await
+                    .ok();
+                break;
+            };
+            match active.frame(msg.as_bytes()) {
+                Ok(Some(samples)) => {
+                    if active.feed.send(samples).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    peer_tx
+                        .send(ServerMessage::Error { error }.into())
+                        .await
+                        .ok();
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(mut session) = session {
+        session.payload.connected = false;
+        app.emit_all("whisper:remote_session", session.payload).ok();
+    }
+    eprintln!("[Audio] Remote microphone disconnected: {}", query.id);
+}
@@ -10,9 +10,10 @@ use tauri::{
 use tokio::sync::mpsc;
 use warp::Filter;
 
-use super::AppConfiguration;
+use super::{voice_commands::VoiceCommands, AppConfiguration};
 
 mod assets;
+mod audio;
 mod peer;
 mod pubsub;
 
@@ -32,6 +33,7 @@ struct WebConfig {
     pub port: String,
     pub peer_path: String,
     pub pubsub_path: String,
+    pub audio_path: String,
 }
 
 #[command]
@@ -44,6 +46,7 @@ async fn config(config: State<'_, AppConfiguration>) -> Result<WebConfig, String
         port: config.port.to_string(),
         peer_path: "peer".to_string(),
         pubsub_path: "pubsub".to_string(),
+        audio_path: "audio".to_string(),
     });
 }
 
@@ -90,7 +93,8 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
                 let routes = warp::path!("ping")
                     .map(|| format!("pong"))
                     .or(peer::path())
-                    .or(pubsub::path(pubsub_input_rx, pubsub_output_tx, app_handle))
+                    .or(pubsub::path(pubsub_input_rx, pubsub_output_tx))
+                    .or(audio::path(app_handle))
                     .or(assets::path(a));
 
                 loop {
@@ -100,10 +104,18 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
                 }
             });
             let handle = app.app_handle();
+            let voice_commands = app.state::<VoiceCommands>().inner().clone();
             tauri::async_runtime::spawn(async move {
                 loop {
                     if let Some(output) = pubsub_output_rx.recv().await {
-                        handle.emit_all("pubsub", output).ok();
+                        // stt finals from peers go through the voice commands first
+                        let (commands, output) = voice_commands.process_pubsub(output);
+                        for command in commands {
+                            handle.emit_all("voice_command", command).ok();
+                        }
+                        if let Some(output) = output {
+                            handle.emit_all("pubsub", output).ok();
+                        }
                     }
                 }
             });
@@ -2,7 +2,7 @@ use std::{collections::HashMap, sync::Arc};
 
 use futures::StreamExt;
 use serde::Deserialize;
-use tauri::{async_runtime::RwLock, AppHandle, Manager, Runtime};
+use tauri::async_runtime::RwLock;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use warp::{
@@ -11,8 +11,6 @@ use warp::{
     Filter, Reply,
 };
 
-use crate::services::whisper::{process_audio_chunk, WhisperState};
-
 #[derive(Deserialize)]
 pub struct PeerQueryData {
     id: String,
@@ -20,7 +18,7 @@ pub struct PeerQueryData {
 
 pub type Peers = Arc<RwLock<HashMap<String, mpsc::UnboundedSender<Result<Message, warp::Error>>>>>;
 
-pub fn path<R: Runtime>(mut input: mpsc::Receiver<String>, output: mpsc::Sender<String>, app: AppHandle<R>) -> BoxedFilter<(impl Reply,)> {
+pub fn path(mut input: mpsc::Receiver<String>, output: mpsc::Sender<String>) -> BoxedFilter<(impl Reply,)> {
     let peers = Peers::default();
 
     let input_peers = peers.clone();
@@ -38,19 +36,17 @@ pub fn path<R: Runtime>(mut input: mpsc::Receiver<String>, output: mpsc::Sender<
 
     let peers = warp::any().map(move || peers.clone());
     let output = warp::any().map(move || output.clone());
-    let app = warp::any().map(move || app.clone());
     let t = warp::path("pubsub")
         .and(warp::ws())
         .and(peers)
         .and(output)
-        .and(app)
         .and(warp::query::<PeerQueryData>())
-        .map(|ws: Ws, peers, output, app, q| ws.on_upgrade(move |socket| peer_handler(socket, peers, output, app, q)))
+        .map(|ws: Ws, peers, output, q| ws.on_upgrade(move |socket| peer_handler(socket, peers, output, q)))
         .boxed();
     t
 }
 
-pub async fn peer_handler<R: Runtime>(ws: WebSocket, peers: Peers, output: mpsc::Sender<String>, app: AppHandle<R>, query: PeerQueryData) {
+pub async fn peer_handler(ws: WebSocket, peers: Peers, output: mpsc::Sender<String>, query: PeerQueryData) {
     eprintln!("[PubSub] New peer connection request: {}", query.id);
     let (peer_tx, mut peer_rx) = ws.split();
 
@@ -70,27 +66,6 @@ pub async fn peer_handler<R: Runtime>(ws: WebSocket, peers: Peers, output: mpsc:
             break;
         };
 
-        if msg.is_binary() {
-            let bytes = msg.as_bytes();
-            eprintln!("[PubSub] Received binary message: {} bytes", bytes.len());
-
-            // Convert bytes to f32 (assuming Little Endian Float32)
-            if bytes.len() % 4 == 0 {
-                let chunks: Vec<f32> = bytes
-                    .chunks_exact(4)
-                    .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-                    .collect();
-
-                // eprintln!("[PubSub] Converted to {} samples", chunks.len());
-
-                // Feed to Whisper
-                if let Some(state) = app.try_state::<WhisperState>() {
-                    process_audio_chunk(&state, &app, chunks);
-                }
-            }
-            continue;
-        }
-
         let Ok(msg_str) = msg.to_str() else { break };
         output.send(msg_str.to_string()).await.ok();
         let p = peers.read().await;
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use whisper_rs::{WhisperContext, WhisperContextParameters};
+
+use super::{
+    decode::DecodeConfig,
+    dsp::WHISPER_SAMPLE_RATE,
+    metrics,
+    transcriber::{Transcriber, WhisperTranscriber},
+};
+
+/// Bundled clip the models are timed on, relative to the resource dir.
+pub const SAMPLE: &str = "resources/whisper/benchmark.wav";
+
+/// Payload of `whisper:benchmark_progress`, sent before each model runs.
+#[derive(Clone, Serialize)]
+pub struct BenchmarkProgress {
+    pub model: String,
+    pub index: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct BenchmarkResult {
+    pub model: String,
+    pub audio_ms: u64,
+    pub load_ms: u64,
+    pub inference_ms: u64,
+    pub rtf: f32,
+    pub text: String,
+    /// Set when the model failed to load or run, the timings are 0 then.
+    pub error: Option<String>,
+}
+
+/// Loads the model into its own context, away from the one live sessions use, and times one run over `audio`.
+pub fn run(model: &str, path: &Path, decode_config: &DecodeConfig, audio: &[f32]) -> BenchmarkResult {
+    let audio_ms = audio.len() as u64 * 1000 / WHISPER_SAMPLE_RATE as u64;
+    let mut result = BenchmarkResult {
+        model: model.to_string(),
+        audio_ms,
+        load_ms: 0,
+        inference_ms: 0,
+        rtf: 0.0,
+        text: String::new(),
+        error: None,
+    };
+    let started = Instant::now();
+    let ctx = path
+        .to_str()
+        .ok_or("Invalid model path".to_string())
+        .and_then(|path| {
+            WhisperContext::new_with_params(path, WhisperContextParameters::default()).map_err(|e| format!("Failed to load context: {}", e))
+        });
+    let ctx = match ctx {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    let load_ms = started.elapsed().as_millis() as u64;
+
+    let transcriber = WhisperTranscriber::new(Arc::new(Mutex::new(Some(ctx))));
+    let started = Instant::now();
+    match transcriber.transcribe(decode_config, "", audio) {
+        Ok(transcript) => {
+            result.load_ms = load_ms;
+            result.inference_ms = started.elapsed().as_millis() as u64;
+            result.rtf = metrics::rtf(audio_ms, result.inference_ms);
+            result.text = transcript.text;
+        }
+        Err(e) => result.error = Some(e),
+    }
+    result
+}
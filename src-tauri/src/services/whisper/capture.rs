@@ -0,0 +1,61 @@
+use cpal::{
+    traits::{DeviceTrait, HostTrait},
+    Device, FromSample, Sample, SampleFormat, SizedSample, Stream, SupportedStreamConfig,
+};
+
+pub fn list_input_devices() -> Result<Vec<String>, String> {
+    let host = cpal::default_host();
+    let devices = host.input_devices().map_err(|e| e.to_string())?;
+    Ok(devices.filter_map(|d| d.name().ok()).collect())
+}
+
+/// Same lookup rules as the audio plugin's output devices, "default" picks the system default.
+pub fn find_input_device(device_name: &str) -> Result<Device, String> {
+    let host = cpal::default_host();
+    if device_name == "default" {
+        host.default_input_device()
+            .ok_or("No input device".to_string())
+    } else {
+        let devices = host.input_devices().map_err(|e| e.to_string())?;
+        devices
+            .into_iter()
+            .find(|d| d.name().unwrap_or_default() == device_name)
+            .ok_or("Device not found".to_string())
+    }
+}
+
+fn build<T, D, E>(device: &Device, config: &SupportedStreamConfig, mut on_data: D, mut on_error: E) -> Result<Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+    D: FnMut(Vec<f32>) + Send + 'static,
+    E: FnMut(String) + Send + 'static,
+{
+    device
+        .build_input_stream(
+            &config.config(),
+            move |data: &[T], _: &cpal::InputCallbackInfo| on_data(data.iter().map(|s| s.to_sample::<f32>()).collect()),
+            move |e| on_error(e.to_string()),
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
+
+/// Opens an input stream in whatever sample format the device uses, samples reach `on_data` as f32.
+pub fn build_input_stream<D, E>(device: &Device, config: &SupportedStreamConfig, on_data: D, on_error: E) -> Result<Stream, String>
+where
+    D: FnMut(Vec<f32>) + Send + 'static,
+    E: FnMut(String) + Send + 'static,
+{
+    match config.sample_format() {
+        SampleFormat::F32 => build::<f32, _, _>(device, config, on_data, on_error),
+        SampleFormat::F64 => build::<f64, _, _>(device, config, on_data, on_error),
+        SampleFormat::I8 => build::<i8, _, _>(device, config, on_data, on_error),
+        SampleFormat::I16 => build::<i16, _, _>(device, config, on_data, on_error),
+        SampleFormat::I32 => build::<i32, _, _>(device, config, on_data, on_error),
+        SampleFormat::U8 => build::<u8, _, _>(device, config, on_data, on_error),
+        SampleFormat::U16 => build::<u16, _, _>(device, config, on_data, on_error),
+        SampleFormat::U32 => build::<u32, _, _>(device, config, on_data, on_error),
+        other => Err(format!("Unsupported sample format: {:?}", other)),
+    }
+}
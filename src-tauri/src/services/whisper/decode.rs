@@ -0,0 +1,83 @@
+use serde::Deserialize;
+use whisper_rs::SamplingStrategy;
+
+pub const AUTO_LANGUAGE: &str = "auto";
+const DEFAULT_N_THREADS: i32 = 4;
+/// whisper.cpp only looks at the last few hundred prompt tokens, longer context is wasted work.
+const MAX_CONTEXT_CHARS: usize = 500;
+
+/// Decoding knobs passed to `start_recording`, everything is optional.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DecodeOptions {
+    pub initial_prompt: Option<String>,
+    /// Names and jargon that should be spelled a certain way, turned into a prompt.
+    pub vocabulary: Vec<String>,
+    /// Feed the previous utterance back in as context.
+    pub carry_context: bool,
+    /// Beam search with this many beams, greedy when unset.
+    pub beam_size: Option<i32>,
+    pub best_of: Option<i32>,
+    pub temperature: Option<f32>,
+    /// Temperature step used when a decode fails its checks, 0 disables the fallback.
+    pub temperature_inc: Option<f32>,
+    pub n_threads: Option<i32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DecodeConfig {
+    /// Whisper language code, or "auto" to detect it per chunk.
+    pub language: String,
+    pub translate: bool,
+    pub options: DecodeOptions,
+}
+
+impl Default for DecodeConfig {
+    fn default() -> Self {
+        Self {
+            language: "en".to_string(),
+            translate: false,
+            options: DecodeOptions::default(),
+        }
+    }
+}
+
+impl DecodeConfig {
+    pub fn n_threads(&self) -> i32 {
+        self.options.n_threads.unwrap_or(DEFAULT_N_THREADS).max(1)
+    }
+
+    pub fn sampling_strategy(&self) -> SamplingStrategy {
+        match self.options.beam_size {
+            Some(beam_size) if beam_size > 1 => SamplingStrategy::BeamSearch { beam_size, patience: -1.0 },
+            _ => SamplingStrategy::Greedy {
+                best_of: self.options.best_of.unwrap_or(1).max(1),
+            },
+        }
+    }
+
+    /// Builds the initial prompt from the configured prompt, the vocabulary and the previous utterance.
+    pub fn prompt(&self, previous_text: &str) -> String {
+        let mut parts = Vec::new();
+        if let Some(prompt) = self
+            .options
+            .initial_prompt
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+        {
+            parts.push(prompt.trim().to_string());
+        }
+        if !self.options.vocabulary.is_empty() {
+            parts.push(format!("{}.", self.options.vocabulary.join(", ")));
+        }
+        if self.options.carry_context && !previous_text.is_empty() {
+            let start = previous_text
+                .char_indices()
+                .rev()
+                .nth(MAX_CONTEXT_CHARS - 1)
+                .map_or(0, |(i, _)| i);
+            parts.push(previous_text[start..].to_string());
+        }
+        parts.join(" ")
+    }
+}
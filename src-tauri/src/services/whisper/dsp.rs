@@ -0,0 +1,303 @@
+use std::f64::consts::PI;
+
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// Zero crossings of the sinc kernel on each side of the output sample.
+const SINC_ZERO_CROSSINGS: f64 = 16.0;
+/// Cutoff relative to the lower of the two Nyquist frequencies, leaves room for the transition band.
+const SINC_ROLLOFF: f64 = 0.94;
+
+/// Averages interleaved frames of any channel count into a mono signal.
+pub fn downmix(data: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return data.to_vec();
+    }
+    let channels = channels as usize;
+    data.chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+pub fn calculate_peak_db(samples: &[f32]) -> f32 {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak > 0.0 {
+        20.0 * peak.log10()
+    } else {
+        -100.0
+    }
+}
+
+pub fn calculate_rms_db(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return -100.0;
+    }
+    let sum_squares: f32 = samples.iter().map(|s| s * s).sum();
+    let rms = (sum_squares / samples.len() as f32).sqrt();
+    if rms > 0.0 {
+        20.0 * rms.log10()
+    } else {
+        -100.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+fn blackman(u: f64) -> f64 {
+    // u is the position inside the window, -1..1
+    0.42 + 0.5 * (PI * u).cos() + 0.08 * (2.0 * PI * u).cos()
+}
+
+fn kernel(ratio: f64) -> (f64, f64) {
+    let cutoff = ratio.min(1.0) * SINC_ROLLOFF;
+    (cutoff, SINC_ZERO_CROSSINGS / cutoff)
+}
+
+/// Input samples outside the slice count as silence.
+fn interpolate(input: &[f32], center: f64, cutoff: f64, half_width: f64) -> f32 {
+    let start = ((center - half_width).ceil() as i64).max(0);
+    let end = ((center + half_width).floor() as i64).min(input.len() as i64 - 1);
+    let mut acc = 0.0;
+    for k in start..=end {
+        let distance = center - k as f64;
+        acc += input[k as usize] as f64 * cutoff * sinc(cutoff * distance) * blackman(distance / half_width);
+    }
+    acc as f32
+}
+
+/// Band-limited resampling with a Blackman-windowed sinc kernel.
+/// When downsampling the kernel cutoff follows the target Nyquist so content above it is filtered instead of aliased.
+pub fn resample(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || input.is_empty() || from_rate == 0 || to_rate == 0 {
+        return input.to_vec();
+    }
+    let ratio = to_rate as f64 / from_rate as f64;
+    let (cutoff, half_width) = kernel(ratio);
+    let out_len = (input.len() as f64 * ratio).round() as usize;
+    (0..out_len)
+        .map(|n| interpolate(input, n as f64 / ratio, cutoff, half_width))
+        .collect()
+}
+
+/// Same kernel as `resample` for audio that arrives in pieces. Enough input is kept between calls
+/// that piece boundaries do not show up in the output, at the cost of half a kernel of latency.
+pub struct StreamResampler {
+    ratio: f64,
+    cutoff: f64,
+    half_width: f64,
+    buffer: Vec<f32>,
+    /// Input position of the next output sample, relative to the start of `buffer`.
+    position: f64,
+}
+
+impl StreamResampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let ratio = if from_rate == 0 || to_rate == 0 {
+            1.0
+        } else {
+            to_rate as f64 / from_rate as f64
+        };
+        let (cutoff, half_width) = kernel(ratio);
+        Self {
+            ratio,
+            cutoff,
+            half_width,
+            buffer: Vec::new(),
+            position: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.ratio == 1.0 {
+            return input.to_vec();
+        }
+        self.buffer.extend_from_slice(input);
+        let mut output = Vec::new();
+        let last = self.buffer.len() as f64 - 1.0;
+        while self.position + self.half_width <= last {
+            output.push(interpolate(&self.buffer, self.position, self.cutoff, self.half_width));
+            self.position += 1.0 / self.ratio;
+        }
+        let consumed = ((self.position - self.half_width).ceil().max(0.0) as usize).min(self.buffer.len());
+        self.buffer.drain(..consumed);
+        self.position -= consumed as f64;
+        output
+    }
+
+    /// Emits the held back tail at the end of the stream, as if silence followed.
+    pub fn finish(&mut self) -> Vec<f32> {
+        let last = self.buffer.len() as f64 - 1.0;
+        let mut output = Vec::new();
+        while self.position <= last {
+            output.push(interpolate(&self.buffer, self.position, self.cutoff, self.half_width));
+            self.position += 1.0 / self.ratio;
+        }
+        self.buffer.clear();
+        self.position = 0.0;
+        output
+    }
+}
+
+/// In-place radix-2 FFT, `re` and `im` must have the same power of two length.
+/// The inverse is unscaled, divide by the length to get the input back.
+pub fn fft(re: &mut [f32], im: &mut [f32], inverse: bool) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] as f64 * w_re - im[b] as f64 * w_im;
+                let t_im = re[b] as f64 * w_im + im[b] as f64 * w_re;
+                re[b] = (re[a] as f64 - t_re) as f32;
+                im[b] = (im[a] as f64 - t_im) as f32;
+                re[a] = (re[a] as f64 + t_re) as f32;
+                im[a] = (im[a] as f64 + t_im) as f32;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Converts captured interleaved audio into the 16 kHz mono signal whisper expects.
+pub fn to_whisper_input(data: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
+    resample(&downmix(data, channels), sample_rate, WHISPER_SAMPLE_RATE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, rate: u32, secs: f64) -> Vec<f32> {
+        let len = (rate as f64 * secs) as usize;
+        (0..len)
+            .map(|i| (2.0 * PI * freq * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        (samples.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    /// RMS difference against the ideal signal, ignoring the kernel's edge effects.
+    fn error_against(actual: &[f32], expected: &[f32]) -> f64 {
+        let margin = 200;
+        let len = actual.len().min(expected.len()) - margin;
+        let diff: Vec<f32> = (margin..len).map(|i| actual[i] - expected[i]).collect();
+        rms(&diff)
+    }
+
+    #[test]
+    fn downmix_averages_all_channels() {
+        let frames = [1.0, 0.0, -1.0, 0.5, 0.25, 0.25, 0.25, 0.25];
+        assert_eq!(downmix(&frames, 4), vec![0.125, 0.25]);
+        assert_eq!(downmix(&frames, 1), frames.to_vec());
+    }
+
+    #[test]
+    fn downmix_six_channels_keeps_frame_count() {
+        let data = vec![0.5; 6 * 480];
+        let mono = downmix(&data, 6);
+        assert_eq!(mono.len(), 480);
+        assert!(mono.iter().all(|s| (s - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn resample_48k_keeps_sine_in_band() {
+        let output = resample(&sine(440.0, 48000, 1.0), 48000, 16000);
+        assert_eq!(output.len(), 16000);
+        assert!(error_against(&output, &sine(440.0, 16000, 1.0)) < 0.01);
+    }
+
+    #[test]
+    fn resample_44k1_keeps_sine_in_band() {
+        let output = resample(&sine(1000.0, 44100, 1.0), 44100, 16000);
+        assert_eq!(output.len(), 16000);
+        assert!(error_against(&output, &sine(1000.0, 16000, 1.0)) < 0.01);
+    }
+
+    #[test]
+    fn resample_upsamples_8k() {
+        let output = resample(&sine(300.0, 8000, 1.0), 8000, 16000);
+        assert_eq!(output.len(), 16000);
+        assert!(error_against(&output, &sine(300.0, 16000, 1.0)) < 0.01);
+    }
+
+    #[test]
+    fn resample_filters_content_above_target_nyquist() {
+        // 12 kHz would fold down to 4 kHz with nearest-neighbour picking
+        let output = resample(&sine(12000.0, 48000, 1.0), 48000, 16000);
+        assert!(rms(&output[200..output.len() - 200]) < 0.01);
+    }
+
+    #[test]
+    fn stream_resampler_matches_one_shot() {
+        let input = sine(440.0, 48000, 1.0);
+        let expected = resample(&input, 48000, 16000);
+        let mut resampler = StreamResampler::new(48000, 16000);
+        let output: Vec<f32> = input
+            .chunks(441)
+            .flat_map(|chunk| resampler.process(chunk))
+            .collect();
+        // the tail is held back until more input arrives
+        assert!(expected.len() - output.len() < 40);
+        let diff: Vec<f32> = output.iter().zip(&expected).map(|(a, b)| a - b).collect();
+        assert!(rms(&diff) < 1e-6);
+
+        let output: Vec<f32> = output.into_iter().chain(resampler.finish()).collect();
+        assert_eq!(output.len(), expected.len());
+        let diff: Vec<f32> = output.iter().zip(&expected).map(|(a, b)| a - b).collect();
+        assert!(rms(&diff) < 1e-6);
+    }
+
+    #[test]
+    fn fft_matches_naive_dft_and_inverts() {
+        let input: Vec<f32> = (0..64).map(|i| ((i * 7 % 13) as f32 - 6.0) / 6.0).collect();
+        let mut re = input.clone();
+        let mut im = vec![0.0; 64];
+        fft(&mut re, &mut im, false);
+        for k in [0, 1, 5, 32, 63] {
+            let (mut dft_re, mut dft_im) = (0.0, 0.0);
+            for (n, x) in input.iter().enumerate() {
+                let angle = -2.0 * PI * (k * n) as f64 / 64.0;
+                dft_re += *x as f64 * angle.cos();
+                dft_im += *x as f64 * angle.sin();
+            }
+            assert!((re[k] as f64 - dft_re).abs() < 1e-4 && (im[k] as f64 - dft_im).abs() < 1e-4);
+        }
+        fft(&mut re, &mut im, true);
+        for (out, x) in re.iter().zip(&input) {
+            assert!((out / 64.0 - x).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn to_whisper_input_handles_interleaved_stereo() {
+        let mono = sine(440.0, 48000, 0.5);
+        let stereo: Vec<f32> = mono.iter().flat_map(|s| [*s, *s]).collect();
+        let output = to_whisper_input(&stereo, 48000, 2);
+        assert_eq!(output.len(), 8000);
+        assert!(error_against(&output, &sine(440.0, 16000, 0.5)) < 0.01);
+    }
+}
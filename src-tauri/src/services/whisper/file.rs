@@ -0,0 +1,244 @@
+use rodio::{Decoder, Source};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{
+    dsp::{self, WHISPER_SAMPLE_RATE},
+    transcript::{Segment, Transcript},
+};
+
+/// Whisper looks at 30 seconds of audio at a time.
+const WINDOW_SECS: usize = 30;
+
+pub struct AudioFile {
+    samples: Box<dyn Iterator<Item = f32> + Send>,
+    sample_rate: u32,
+    channels: u16,
+    pub duration_ms: Option<u64>,
+}
+
+impl AudioFile {
+    /// Decodes the whole file into 16 kHz mono, only meant for short clips.
+    pub fn into_whisper_input(self) -> Vec<f32> {
+        let samples: Vec<f32> = self.samples.collect();
+        dsp::to_whisper_input(&samples, self.sample_rate, self.channels)
+    }
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |e| e.eq_ignore_ascii_case("wav"))
+}
+
+fn open_wav(path: &Path) -> Result<AudioFile, String> {
+    let reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let duration_ms = reader.duration() as u64 * 1000 / spec.sample_rate as u64;
+    let samples: Box<dyn Iterator<Item = f32> + Send> = match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(reader.into_samples::<f32>().map_while(Result::ok)),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            Box::new(
+                reader
+                    .into_samples::<i32>()
+                    .map_while(Result::ok)
+                    .map(move |s| s as f32 * scale),
+            )
+        }
+    };
+    Ok(AudioFile {
+        samples,
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        duration_ms: Some(duration_ms),
+    })
+}
+
+/// Opens a WAV, MP3 or OGG file. WAV goes through hound so float and 24 bit files work too.
+pub fn open(path: &Path) -> Result<AudioFile, String> {
+    if is_wav(path) {
+        if let Ok(file) = open_wav(path) {
+            return Ok(file);
+        }
+    }
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let decoder = Decoder::new(BufReader::new(file)).map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels();
+    let duration_ms = decoder.total_duration().map(|d| d.as_millis() as u64);
+    Ok(AudioFile {
+        samples: Box::new(decoder.convert_samples::<f32>()),
+        sample_rate,
+        channels,
+        duration_ms,
+    })
+}
+
+/// Transcribes the file window by window. `transcribe` gets 16 kHz mono audio and the text committed so far,
+/// `on_progress` the milliseconds processed. Returned segment times are relative to the start of the file.
+pub fn transcribe<T, P>(file: AudioFile, cancel: &AtomicBool, mut transcribe: T, mut on_progress: P) -> Result<Vec<Segment>, String>
+where
+    T: FnMut(&[f32], &str) -> Result<Transcript, String>,
+    P: FnMut(u64),
+{
+    let window = WHISPER_SAMPLE_RATE as usize * WINDOW_SECS;
+    let samples_per_ms = WHISPER_SAMPLE_RATE as usize / 1000;
+    let source_chunk = file.sample_rate as usize * file.channels as usize * WINDOW_SECS;
+    let mut samples = file.samples;
+    let mut resampler = dsp::StreamResampler::new(file.sample_rate, WHISPER_SAMPLE_RATE);
+    let mut exhausted = false;
+
+    let mut pending: Vec<f32> = Vec::new();
+    let mut offset_ms = 0;
+    let mut committed_text = String::new();
+    let mut segments = Vec::new();
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        while !exhausted && pending.len() < window {
+            let chunk: Vec<f32> = samples.by_ref().take(source_chunk).collect();
+            exhausted = chunk.len() < source_chunk;
+            pending.extend(resampler.process(&dsp::downmix(&chunk, file.channels)));
+            if exhausted {
+                pending.extend(resampler.finish());
+            }
+        }
+        if pending.is_empty() {
+            break;
+        }
+
+        let take = pending.len().min(window);
+        let is_last = exhausted && take == pending.len();
+        let take_ms = (take / samples_per_ms) as u64;
+        let transcript = transcribe(&pending[..take], &committed_text)?.at(0, offset_ms, offset_ms + take_ms);
+        let mut window_segments = transcript.segments;
+
+        let mut consumed = take;
+        if !is_last && window_segments.len() > 1 {
+            // the last segment is likely cut off by the window edge, decode it again with what follows.
+            // One that starts early in the window is kept, otherwise a window could move on by a few ms only.
+            let last_start = window_segments
+                .last()
+                .map_or(0, |last| last.t0_ms.saturating_sub(offset_ms) as usize * samples_per_ms);
+            if last_start >= take / 2 {
+                window_segments.pop();
+                consumed = last_start.min(take);
+            }
+        }
+        for segment in &window_segments {
+            if !committed_text.is_empty() {
+                committed_text.push(' ');
+            }
+            committed_text.push_str(&segment.text);
+        }
+        segments.extend(window_segments);
+
+        pending.drain(..consumed);
+        offset_ms += (consumed / samples_per_ms) as u64;
+        on_progress(offset_ms);
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn silence(secs: usize, sample_rate: u32, channels: u16) -> AudioFile {
+        let len = secs * sample_rate as usize * channels as usize;
+        AudioFile {
+            samples: Box::new(std::iter::repeat(0.0).take(len)),
+            sample_rate,
+            channels,
+            duration_ms: Some(secs as u64 * 1000),
+        }
+    }
+
+    fn segment(t0_ms: u64, t1_ms: u64, text: &str) -> Segment {
+        Segment {
+            text: text.to_string(),
+            t0_ms,
+            t1_ms,
+            avg_logprob: -0.2,
+            no_speech_probability: None,
+            tokens: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn cut_off_segment_is_decoded_again_with_the_next_window() {
+        let mut calls = Vec::new();
+        let mut progress = Vec::new();
+        let segments = transcribe(
+            silence(50, 16000, 1),
+            &AtomicBool::new(false),
+            |audio, committed| {
+                // every window ends in a segment that starts 20 s in
+                let len_ms = (audio.len() * 1000 / WHISPER_SAMPLE_RATE as usize) as u64;
+                calls.push((len_ms, committed.to_string()));
+                let n = calls.len();
+                let segments = vec![segment(0, 20_000, &format!("a{}", n)), segment(20_000, len_ms, &format!("b{}", n))];
+                Ok(Transcript::new(segments, None, None))
+            },
+            |ms| progress.push(ms),
+        )
+        .unwrap();
+
+        // "b1" is dropped and its audio from 20 s on becomes the start of the last window
+        assert_eq!(calls, [(30_000, String::new()), (30_000, "a1".to_string())]);
+        assert_eq!(progress, [20_000, 50_000]);
+        let times: Vec<_> = segments
+            .iter()
+            .map(|s| (s.text.as_str(), s.t0_ms, s.t1_ms))
+            .collect();
+        assert_eq!(times, [("a1", 0, 20_000), ("a2", 20_000, 40_000), ("b2", 40_000, 50_000)]);
+    }
+
+    #[test]
+    fn keeps_a_last_segment_that_starts_early_in_the_window() {
+        let mut progress = Vec::new();
+        let segments = transcribe(
+            silence(70, 16000, 1),
+            &AtomicBool::new(false),
+            |audio, _| {
+                let len_ms = (audio.len() * 1000 / WHISPER_SAMPLE_RATE as usize) as u64;
+                Ok(Transcript::new(vec![segment(0, 0, "a"), segment(0, len_ms, "b")], None, None))
+            },
+            |ms| progress.push(ms),
+        )
+        .unwrap();
+
+        assert_eq!(progress, [30_000, 60_000, 70_000]);
+        assert_eq!(segments.len(), 6);
+    }
+
+    #[test]
+    fn resamples_the_whole_file_without_losing_samples() {
+        let mut total = 0;
+        let mut progress = 0;
+        transcribe(
+            silence(70, 44100, 2),
+            &AtomicBool::new(false),
+            |audio, _| {
+                total += audio.len();
+                Ok(Transcript::new(vec![segment(0, 1000, "x")], None, None))
+            },
+            |ms| progress = ms,
+        )
+        .unwrap();
+        assert_eq!(total, 70 * WHISPER_SAMPLE_RATE as usize);
+        assert_eq!(progress, 70_000);
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let result = transcribe(silence(5, 16000, 1), &AtomicBool::new(true), |_, _| unreachable!(), |_| {});
+        assert_eq!(result.unwrap_err(), "Cancelled");
+    }
+}
@@ -0,0 +1,309 @@
+use flate2::{write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+use super::transcript::{Segment, Transcript};
+
+/// Longest phrase, in words, checked for loops.
+const MAX_NGRAM: usize = 6;
+
+/// Phrases whisper likes to produce from silence and music, compared after normalization.
+const DEFAULT_BLOCKLIST: &[&str] = &[
+    "thank you for watching",
+    "thanks for watching",
+    "thank you so much for watching",
+    "please subscribe",
+    "like and subscribe",
+    "dont forget to like and subscribe",
+    "see you in the next video",
+    "subtitles by the amaraorg community",
+    "transcription by castingwords",
+    "ご視聴ありがとうございました",
+    "gracias por ver",
+    "untertitel der amaraorg community",
+];
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FilterOptions {
+    pub enabled: bool,
+    /// Segments more likely silence than speech are dropped. Only a `server` engine reports the probability,
+    /// the local whisper.cpp engine leaves it out so this has no effect there.
+    pub no_speech_threshold: f32,
+    pub logprob_threshold: f32,
+    /// A word or phrase repeated back to back more often than this marks a loop.
+    pub max_repeats: usize,
+    /// Text that compresses better than this is repetitive junk, same test whisper itself uses.
+    pub compression_ratio_threshold: f32,
+    pub use_default_blocklist: bool,
+    pub blocklist: Vec<String>,
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            no_speech_threshold: 0.6,
+            logprob_threshold: -1.0,
+            max_repeats: 3,
+            compression_ratio_threshold: 2.4,
+            use_default_blocklist: true,
+            blocklist: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    SoundTag,
+    NoSpeech,
+    LowLogprob,
+    Repetition,
+    Compression,
+    Blocklist,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RejectedSegment {
+    pub text: String,
+    pub t0_ms: u64,
+    pub t1_ms: u64,
+    pub reason: RejectReason,
+}
+
+/// Payload of the `whisper:rejected` debug event.
+#[derive(Clone, Debug, Serialize)]
+pub struct RejectedPayload {
+    pub session: String,
+    pub sequence: u64,
+    pub segments: Vec<RejectedSegment>,
+}
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Removes `[BLANK_AUDIO]`, `(music)` and `♪` style annotations from the text and its tokens.
+fn strip_sound_tags(segment: &mut Segment) {
+    let mut depth = 0i32;
+    let mut text = String::with_capacity(segment.text.len());
+    for c in segment.text.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = (depth - 1).max(0),
+            '♪' => {}
+            _ if depth == 0 => text.push(c),
+            _ => {}
+        }
+    }
+    segment.text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    depth = 0;
+    segment.tokens.retain(|token| {
+        let inside = depth > 0 || token.text.contains(['[', '(', '♪']);
+        for c in token.text.chars() {
+            match c {
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth = (depth - 1).max(0),
+                _ => {}
+            }
+        }
+        !inside && !token.text.contains([']', ')'])
+    });
+}
+
+fn has_loop(text: &str, max_repeats: usize) -> bool {
+    let words: Vec<String> = normalize(text).split(' ').map(str::to_string).collect();
+    for n in 1..=MAX_NGRAM {
+        for start in 0..words.len() {
+            let mut count = 1;
+            while start + (count + 1) * n <= words.len() && words[start..start + n] == words[start + count * n..start + (count + 1) * n] {
+                count += 1;
+            }
+            if count > max_repeats {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn compression_ratio(text: &str) -> f32 {
+    let bytes = text.as_bytes();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    if encoder.write_all(bytes).is_err() {
+        return 0.0;
+    }
+    match encoder.finish() {
+        Ok(compressed) if !compressed.is_empty() => bytes.len() as f32 / compressed.len() as f32,
+        _ => 0.0,
+    }
+}
+
+fn check(options: &FilterOptions, segment: &Segment) -> Option<RejectReason> {
+    if segment.text.is_empty() {
+        return Some(RejectReason::SoundTag);
+    }
+    if segment
+        .no_speech_probability
+        .map_or(false, |p| p > options.no_speech_threshold)
+    {
+        return Some(RejectReason::NoSpeech);
+    }
+    if !segment.tokens.is_empty() && segment.avg_logprob < options.logprob_threshold {
+        return Some(RejectReason::LowLogprob);
+    }
+    if has_loop(&segment.text, options.max_repeats) {
+        return Some(RejectReason::Repetition);
+    }
+    if compression_ratio(&segment.text) > options.compression_ratio_threshold {
+        return Some(RejectReason::Compression);
+    }
+    let normalized = normalize(&segment.text);
+    let blocked = options.blocklist.iter().any(|p| normalize(p) == normalized)
+        || (options.use_default_blocklist && DEFAULT_BLOCKLIST.iter().any(|p| *p == normalized));
+    if blocked {
+        return Some(RejectReason::Blocklist);
+    }
+    None
+}
+
+/// Drops hallucinated segments from the transcript and returns what was removed.
+pub fn apply(options: &FilterOptions, transcript: &mut Transcript) -> Vec<RejectedSegment> {
+    if !options.enabled {
+        return Vec::new();
+    }
+    let mut rejected = Vec::new();
+    let segments = std::mem::take(&mut transcript.segments);
+    for mut segment in segments {
+        let original = segment.text.clone();
+        strip_sound_tags(&mut segment);
+        match check(options, &segment) {
+            Some(reason) => rejected.push(RejectedSegment {
+                text: original,
+                t0_ms: segment.t0_ms,
+                t1_ms: segment.t1_ms,
+                reason,
+            }),
+            None => transcript.segments.push(segment),
+        }
+    }
+    transcript.rebuild_text();
+    rejected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::transcript::Token;
+    use super::*;
+
+    fn token(text: &str) -> Token {
+        Token {
+            text: text.to_string(),
+            probability: 0.9,
+            t0_ms: 0,
+            t1_ms: 0,
+        }
+    }
+
+    fn segment(text: &str, tokens: &[&str]) -> Segment {
+        Segment {
+            text: text.to_string(),
+            t0_ms: 0,
+            t1_ms: 1000,
+            avg_logprob: -0.2,
+            no_speech_probability: None,
+            tokens: tokens.iter().map(|t| token(t)).collect(),
+        }
+    }
+
+    #[test]
+    fn finds_repeated_words_and_phrases() {
+        assert!(has_loop("no no no no", 3));
+        assert!(!has_loop("no no no", 3));
+        assert!(has_loop("I mean, I mean. I mean I mean", 3));
+        assert!(has_loop("so we go to the shop we go to the shop we go to the shop we go to the shop", 3));
+        assert!(!has_loop("the cat sat on the mat with the other cat", 3));
+        assert!(!has_loop("", 3));
+    }
+
+    #[test]
+    fn keeps_short_sincere_repeats() {
+        let mut transcript = Transcript::new(
+            vec![
+                segment("Thank you, thank you.", &[" Thank", " you", ",", " thank", " you", "."]),
+                segment("No, no, no.", &[" No", ",", " no", ",", " no", "."]),
+                segment("It was really, really good.", &[]),
+            ],
+            None,
+            None,
+        );
+        let rejected = apply(&FilterOptions::default(), &mut transcript);
+        assert!(rejected.is_empty(), "{:?}", rejected);
+        assert_eq!(transcript.text, "Thank you, thank you. No, no, no. It was really, really good.");
+    }
+
+    #[test]
+    fn repetitive_text_compresses_well() {
+        let looped = "thank you ".repeat(20);
+        assert!(compression_ratio(&looped) > 2.4);
+        assert!(compression_ratio("The quick brown fox jumps over the lazy dog.") < 2.4);
+        assert_eq!(compression_ratio(""), 0.0);
+    }
+
+    #[test]
+    fn strips_tags_from_text_and_tokens() {
+        let mut tagged = segment(
+            "[BLANK_AUDIO] Hello (music) there ♪",
+            &["[", "BLANK", "_AUDIO", "]", " Hello", " (", "music", ")", " there", " ♪"],
+        );
+        strip_sound_tags(&mut tagged);
+        assert_eq!(tagged.text, "Hello there");
+        let tokens: Vec<_> = tagged.tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(tokens, [" Hello", " there"]);
+
+        let mut tag_only = segment("[Music]", &[]);
+        strip_sound_tags(&mut tag_only);
+        assert_eq!(tag_only.text, "");
+    }
+
+    #[test]
+    fn blocklist_ignores_case_and_punctuation() {
+        assert_eq!(normalize("  Thanks for   WATCHING! "), "thanks for watching");
+        assert_eq!(normalize("Don't forget to like & subscribe."), "dont forget to like subscribe");
+
+        let options = FilterOptions {
+            blocklist: vec!["Bye, everyone!".to_string()],
+            ..FilterOptions::default()
+        };
+        let mut transcript = Transcript::new(
+            vec![
+                segment("Thank you for watching.", &[]),
+                segment("bye everyone", &[]),
+                segment("Bye, see you tomorrow.", &[]),
+            ],
+            None,
+            None,
+        );
+        let rejected = apply(&options, &mut transcript);
+        assert_eq!(transcript.text, "Bye, see you tomorrow.");
+        assert_eq!(rejected.len(), 2);
+        assert!(rejected
+            .iter()
+            .all(|r| matches!(r.reason, RejectReason::Blocklist)));
+
+        let options = FilterOptions {
+            use_default_blocklist: false,
+            ..FilterOptions::default()
+        };
+        let mut transcript = Transcript::new(vec![segment("Thank you for watching.", &[])], None, None);
+        assert!(apply(&options, &mut transcript).is_empty());
+    }
+}
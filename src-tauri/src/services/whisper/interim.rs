@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+#[derive(Clone, Serialize)]
+pub struct InterimPayload {
+    pub session: String,
+    /// Sequence id of the `whisper:final` result this utterance will end in.
+    pub sequence: u64,
+    pub text: String,
+    pub stable: String,
+    pub unstable: String,
+}
+
+/// Tracks successive hypotheses of the same utterance.
+/// Words two hypotheses in a row agree on become stable and are never taken back.
+#[derive(Default)]
+pub struct InterimTracker {
+    committed: Vec<String>,
+    previous: Vec<String>,
+}
+
+impl InterimTracker {
+    pub fn reset(&mut self) {
+        self.committed.clear();
+        self.previous.clear();
+    }
+
+    pub fn update(&mut self, session: &str, sequence: u64, hypothesis: &str) -> InterimPayload {
+        let words: Vec<String> = hypothesis.split_whitespace().map(str::to_string).collect();
+        let agreed = self
+            .previous
+            .iter()
+            .zip(words.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if agreed > self.committed.len() {
+            self.committed = words[..agreed].to_vec();
+        }
+        self.previous = words;
+
+        let stable = self.committed.join(" ");
+        let unstable = self
+            .previous
+            .iter()
+            .skip(self.committed.len())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let text = [stable.as_str(), unstable.as_str()]
+            .iter()
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        InterimPayload {
+            session: session.to_string(),
+            sequence,
+            text,
+            stable,
+            unstable,
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use serde::Serialize;
+
+use super::dsp::{calculate_peak_db, calculate_rms_db, downmix};
+
+pub const DEFAULT_LEVEL_INTERVAL_MS: u64 = 100;
+
+/// Frame length the calibration looks at, same as the VAD default.
+const CALIBRATION_FRAME_MS: u64 = 20;
+/// Below this gap between room noise and speech no threshold separates the two reliably.
+const MIN_SPEECH_MARGIN_DB: f32 = 6.0;
+/// The threshold has to clear the noise by at least this much.
+const MIN_NOISE_MARGIN_DB: f32 = 3.0;
+/// Added to the longest pause inside speech so a sentence is not cut at a breath.
+const PAUSE_MARGIN_MS: u64 = 300;
+/// Added to the longest noise burst so a bump is not taken for an utterance.
+const BURST_MARGIN_MS: u64 = 100;
+
+/// Payload of `whisper:level`.
+#[derive(Clone, Serialize)]
+pub struct LevelPayload {
+    pub session: String,
+    pub rms_db: f32,
+    pub peak_db: f32,
+    /// The VAD is inside an utterance, always false outside VAD segmentation.
+    pub speech: bool,
+}
+
+/// Collects the downmixed input and reports its level once per interval of audio,
+/// so the event rate does not depend on the capture callback size.
+pub struct LevelMeter {
+    channels: u16,
+    window_len: usize,
+    window: Vec<f32>,
+}
+
+impl LevelMeter {
+    pub fn new(interval_ms: u64, sample_rate: u32, channels: u16) -> Self {
+        let window_len = ((sample_rate as u64 * interval_ms / 1000) as usize).max(1);
+        Self {
+            channels,
+            window_len,
+            window: Vec::with_capacity(window_len),
+        }
+    }
+
+    /// Feeds interleaved samples, returns RMS and peak dB of the last window that filled up.
+    pub fn push(&mut self, data: &[f32]) -> Option<(f32, f32)> {
+        let mut level = None;
+        for sample in downmix(data, self.channels) {
+            self.window.push(sample);
+            if self.window.len() >= self.window_len {
+                level = Some((calculate_rms_db(&self.window), calculate_peak_db(&self.window)));
+                self.window.clear();
+            }
+        }
+        level
+    }
+}
+
+/// Result of `calibrate_vad`, the fields map onto the `start_recording` params.
+#[derive(Clone, Debug, Serialize)]
+pub struct Calibration {
+    pub noise_db: f32,
+    pub speech_db: f32,
+    pub silence_threshold_db: f32,
+    pub silence_duration_ms: u64,
+    pub min_chunk_duration_ms: u64,
+}
+
+fn frame_energies(mono: &[f32], sample_rate: u32) -> Vec<f32> {
+    let frame_len = ((sample_rate as u64 * CALIBRATION_FRAME_MS / 1000) as usize).max(1);
+    mono.chunks_exact(frame_len).map(calculate_rms_db).collect()
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    sorted[index]
+}
+
+/// Lengths in frames of the runs where `matches` holds. With `inner_only` runs touching either end are left out.
+fn runs(energies: &[f32], matches: impl Fn(f32) -> bool, inner_only: bool) -> Vec<u64> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, energy) in energies.iter().enumerate() {
+        match (matches(*energy), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                if !(inner_only && s == 0) {
+                    runs.push((i - s) as u64);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let (Some(s), false) = (start, inner_only) {
+        runs.push((energies.len() - s) as u64);
+    }
+    runs
+}
+
+/// Derives VAD settings from a recording of the quiet room and one of the user talking, both mono.
+pub fn calibrate(noise: &[f32], speech: &[f32], sample_rate: u32) -> Result<Calibration, String> {
+    let noise_energies = frame_energies(noise, sample_rate);
+    let speech_energies = frame_energies(speech, sample_rate);
+    if noise_energies.is_empty() || speech_energies.is_empty() {
+        return Err("Calibration recording is too short".to_string());
+    }
+    // speech has pauses, its loud part is what the threshold has to stay below
+    let noise_db = percentile(&noise_energies, 0.9);
+    let speech_db = percentile(&speech_energies, 0.8);
+    if speech_db - noise_db < MIN_SPEECH_MARGIN_DB {
+        return Err("Speech was not clearly louder than the room noise, move closer to the microphone".to_string());
+    }
+    let silence_threshold_db = (noise_db + (speech_db - noise_db) / 3.0).max(noise_db + MIN_NOISE_MARGIN_DB);
+
+    let pauses = runs(&speech_energies, |e| e <= silence_threshold_db, true);
+    let silence_duration_ms = match pauses.iter().max() {
+        Some(frames) => (frames * CALIBRATION_FRAME_MS + PAUSE_MARGIN_MS).clamp(500, 3000),
+        None => 1500,
+    };
+    let bursts = runs(&noise_energies, |e| e > silence_threshold_db, false);
+    let longest_burst_ms = bursts
+        .iter()
+        .max()
+        .map_or(0, |frames| frames * CALIBRATION_FRAME_MS);
+    let min_chunk_duration_ms = (longest_burst_ms + BURST_MARGIN_MS).clamp(300, 1500);
+
+    Ok(Calibration {
+        noise_db,
+        speech_db,
+        silence_threshold_db,
+        silence_duration_ms,
+        min_chunk_duration_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 16000;
+
+    /// Deterministic white noise at roughly the given RMS.
+    fn noise(secs: f32, rms: f32) -> Vec<f32> {
+        let mut seed: u32 = 0x1234_5678;
+        (0..(RATE as f32 * secs) as usize)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed as f32 / u32::MAX as f32 * 2.0 - 1.0) * rms * 3f32.sqrt()
+            })
+            .collect()
+    }
+
+    fn tone(secs: f32, amplitude: f32) -> Vec<f32> {
+        (0..(RATE as f32 * secs) as usize)
+            .map(|i| amplitude * (2.0 * PI * 220.0 * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    fn mix(a: &[f32], b: &[f32]) -> Vec<f32> {
+        a.iter().zip(b).map(|(a, b)| a + b).collect()
+    }
+
+    #[test]
+    fn meter_reports_once_per_interval() {
+        let mut meter = LevelMeter::new(100, RATE, 2);
+        let stereo: Vec<f32> = tone(1.0, 0.5).iter().flat_map(|s| [*s, *s]).collect();
+        let reports: Vec<(f32, f32)> = stereo
+            .chunks(700)
+            .filter_map(|chunk| meter.push(chunk))
+            .collect();
+        assert_eq!(reports.len(), 10);
+        let (rms_db, peak_db) = reports[5];
+        // a sine's RMS sits 3 dB under its peak
+        assert!((peak_db - 20.0 * 0.5f32.log10()).abs() < 0.1);
+        assert!((peak_db - rms_db - 3.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn threshold_sits_between_noise_and_speech() {
+        let room = noise(3.0, 0.003);
+        let mut speech = tone(1.0, 0.2);
+        speech.extend(vec![0.0; (RATE as f32 * 0.6) as usize]);
+        speech.extend(tone(1.0, 0.2));
+        let speech = mix(&speech, &noise(2.6, 0.003));
+
+        let calibration = calibrate(&room, &speech, RATE).unwrap();
+        assert!(calibration.silence_threshold_db > calibration.noise_db + MIN_NOISE_MARGIN_DB - 0.01);
+        assert!(calibration.silence_threshold_db < calibration.speech_db);
+        // the 600 ms pause must not end the utterance
+        assert!(calibration.silence_duration_ms >= 900, "{:?}", calibration);
+        assert_eq!(calibration.min_chunk_duration_ms, 300);
+    }
+
+    #[test]
+    fn noise_bursts_raise_min_chunk_duration() {
+        // the burst is under a tenth of the recording, so it does not lift the noise estimate
+        let mut room = noise(1.0, 0.003);
+        room.extend(mix(&tone(0.4, 0.2), &noise(0.4, 0.003)));
+        room.extend(noise(4.6, 0.003));
+        let speech = mix(&tone(2.0, 0.4), &noise(2.0, 0.003));
+
+        let calibration = calibrate(&room, &speech, RATE).unwrap();
+        assert!(calibration.min_chunk_duration_ms >= 500, "{:?}", calibration);
+    }
+
+    #[test]
+    fn rejects_speech_as_quiet_as_the_room() {
+        let room = noise(2.0, 0.01);
+        let speech = noise(2.0, 0.012);
+        assert!(calibrate(&room, &speech, RATE).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use serde::Serialize;
+
+/// Payload of `whisper:metrics`, sent for every final utterance.
+#[derive(Clone, Debug, Serialize)]
+pub struct MetricsPayload {
+    pub session: String,
+    pub sequence: u64,
+    pub audio_ms: u64,
+    /// Time between the utterance ending and the worker picking it up.
+    pub queue_ms: u64,
+    pub inference_ms: u64,
+    pub rtf: f32,
+}
+
+/// Real-time factor, seconds of compute per second of audio. Above 1 the model falls behind live speech.
+pub fn rtf(audio_ms: u64, inference_ms: u64) -> f32 {
+    if audio_ms == 0 {
+        return 0.0;
+    }
+    inference_ms as f32 / audio_ms as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtf_is_compute_over_audio() {
+        assert_eq!(rtf(4000, 1000), 0.25);
+        assert_eq!(rtf(2000, 3000), 1.5);
+        assert_eq!(rtf(0, 500), 0.0);
+    }
+}
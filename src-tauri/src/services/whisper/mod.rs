@@ -0,0 +1,966 @@
+use cpal::traits::{DeviceTrait, StreamTrait};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{
+    command,
+    plugin::{Builder, TauriPlugin},
+    AppHandle, Manager, Runtime, State,
+};
+use whisper_rs::{WhisperContext, WhisperContextParameters};
+
+use crate::services::voice_commands::VoiceCommands;
+
+use self::{
+    benchmark::{BenchmarkProgress, BenchmarkResult},
+    decode::{DecodeConfig, DecodeOptions, AUTO_LANGUAGE},
+    filter::FilterOptions,
+    interim::InterimTracker,
+    level::{Calibration, DEFAULT_LEVEL_INTERVAL_MS},
+    preprocess::{PreprocessOptions, Preprocessor},
+    queue::{BacklogPolicy, InferenceQueue},
+    recorder::RecorderOptions,
+    residency::{ModelSlot, ModelStatus, UnloadReason, UnloadedPayload},
+    server::ServerOptions,
+    session::{Segmentation, Session, SessionConfig, SessionInfo, DEFAULT_SESSION},
+    subtitles::SubtitleFormat,
+    transcriber::{Transcriber, WhisperTranscriber},
+    transcript::Transcript,
+    vad::VadConfig,
+};
+
+mod benchmark;
+mod capture;
+mod decode;
+mod dsp;
+mod file;
+mod filter;
+mod interim;
+mod level;
+mod metrics;
+mod models;
+mod pipeline;
+mod preprocess;
+mod queue;
+mod recorder;
+mod remote;
+mod residency;
+mod server;
+mod session;
+mod subtitles;
+mod transcriber;
+mod transcript;
+mod vad;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::remote::RemoteSource;
+
+#[derive(Clone)]
+pub struct WhisperState {
+    sessions: Arc<Mutex<HashMap<String, Arc<Session>>>>,
+    queue: Arc<InferenceQueue>,
+    ctx: Arc<Mutex<Option<WhisperContext>>>,
+    /// Always locked after `ctx`.
+    model: Arc<Mutex<Option<ModelSlot>>>,
+    /// Unload the model after this long without recording, None keeps it loaded.
+    idle_timeout: Arc<Mutex<Option<Duration>>>,
+    downloads: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    /// Cancel flags of running file transcriptions, keyed by source path.
+    file_jobs: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    /// Set for the whole benchmark run, `start_recording` refuses to start meanwhile.
+    benchmarking: Arc<AtomicBool>,
+}
+
+impl WhisperState {
+    fn new() -> Self {
+        Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            queue: Arc::new(InferenceQueue::default()),
+            ctx: Arc::new(Mutex::new(None)),
+            model: Arc::new(Mutex::new(None)),
+            idle_timeout: Arc::new(Mutex::new(None)),
+            downloads: Arc::new(Mutex::new(HashMap::new())),
+            file_jobs: Arc::new(Mutex::new(HashMap::new())),
+            benchmarking: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn session(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    fn transcriber(&self) -> WhisperTranscriber {
+        WhisperTranscriber::new(self.ctx.clone())
+    }
+
+    fn is_recording(&self) -> bool {
+        !self.sessions.lock().unwrap().is_empty()
+    }
+
+    /// A session transcribes with the loaded model, server sessions do not count.
+    fn is_using_model(&self) -> bool {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .any(|session| session.server.is_none())
+    }
+
+    /// Queue the session's utterances go to, the shared one unless it has its own.
+    fn queue_for(&self, session: &Session) -> Arc<InferenceQueue> {
+        session.queue.clone().unwrap_or_else(|| self.queue.clone())
+    }
+
+    fn is_busy(&self) -> bool {
+        self.is_recording() || !self.file_jobs.lock().unwrap().is_empty()
+    }
+
+    /// Restarts the idle timeout.
+    fn touch_model(&self) {
+        if let Some(slot) = self.model.lock().unwrap().as_mut() {
+            slot.touch();
+        }
+    }
+}
+
+fn load_model(state: &WhisperState, model: &str, model_path: &Path) -> Result<(), String> {
+    let mut ctx_guard = state.ctx.lock().map_err(|_| "Failed to lock ctx")?;
+    let mut slot_guard = state.model.lock().map_err(|_| "Failed to lock model")?;
+    if let Some(slot) = slot_guard
+        .as_mut()
+        .filter(|slot| ctx_guard.is_some() && slot.id == model)
+    {
+        slot.touch();
+        return Ok(());
+    }
+    if ctx_guard.is_some() && state.is_using_model() {
+        return Err(format!(
+            "Model {} is in use by a running session",
+            slot_guard
+                .as_ref()
+                .map(|slot| slot.id.as_str())
+                .unwrap_or_default()
+        ));
+    }
+    // free the old weights before the new ones come in
+    *ctx_guard = None;
+    let path_str = model_path.to_str().ok_or("Invalid model path")?;
+    let ctx = WhisperContext::new_with_params(path_str, WhisperContextParameters::default()).map_err(|e| format!("Failed to load context: {}", e))?;
+    *ctx_guard = Some(ctx);
+    *slot_guard = Some(ModelSlot::new(model, model_path));
+    Ok(())
+}
+
+fn load_installed_model<R: Runtime>(app: &AppHandle<R>, state: &WhisperState, model: &str) -> Result<(), String> {
+    let model_path = models::installed_path(app, model)?;
+    load_model(state, model, &model_path)
+}
+
+/// Loads the named model, or brings back the last one when an unload dropped it.
+fn prepare_model<R: Runtime>(app: &AppHandle<R>, state: &WhisperState, model: Option<&str>) -> Result<(), String> {
+    if let Some(model) = model {
+        return load_installed_model(app, state, model);
+    }
+    let last = state
+        .model
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|slot| (slot.id.clone(), slot.path.clone()));
+    match last {
+        Some((id, path)) => load_model(state, &id, &path),
+        None => Ok(()),
+    }
+}
+
+/// Drops the context and keeps the slot, so the next start can load it again. Returns the unloaded model.
+fn unload_model_if(state: &WhisperState, idle: Option<Duration>) -> Result<Option<String>, String> {
+    let mut ctx_guard = state.ctx.lock().map_err(|_| "Failed to lock ctx")?;
+    let slot_guard = state.model.lock().map_err(|_| "Failed to lock model")?;
+    let Some(slot) = slot_guard.as_ref().filter(|_| ctx_guard.is_some()) else {
+        return Ok(None);
+    };
+    if idle.map_or(false, |timeout| slot.idle_for() < timeout) {
+        return Ok(None);
+    }
+    if state.is_busy() {
+        return Err(format!("Model {} is in use", slot.id));
+    }
+    *ctx_guard = None;
+    Ok(Some(slot.id.clone()))
+}
+
+/// Unloads the model once it sat unused for the idle timeout.
+fn idle_watcher<R: Runtime>(app: AppHandle<R>, state: WhisperState) {
+    loop {
+        thread::sleep(residency::IDLE_CHECK_INTERVAL);
+        let Some(timeout) = *state.idle_timeout.lock().unwrap() else {
+            continue;
+        };
+        if let Ok(Some(model)) = unload_model_if(&state, Some(timeout)) {
+            let _ = app.emit_all(
+                "whisper:model_unloaded",
+                UnloadedPayload {
+                    model,
+                    reason: UnloadReason::Idle,
+                },
+            );
+        }
+    }
+}
+
+/// Validates the language and task against each other, and against the loaded model when `uses_model` is set.
+fn build_decode_config(
+    state: &WhisperState,
+    language: Option<String>,
+    task: Option<String>,
+    decoding: Option<DecodeOptions>,
+    uses_model: bool,
+) -> Result<DecodeConfig, String> {
+    let language = language.unwrap_or_else(|| "en".to_string());
+    if language != AUTO_LANGUAGE && whisper_rs::get_lang_id(&language).is_none() {
+        return Err(format!("Unsupported language: {}", language));
+    }
+    let translate = match task.as_deref() {
+        None | Some("transcribe") => false,
+        Some("translate") => true,
+        Some(other) => return Err(format!("Unknown task: {}", other)),
+    };
+    let ctx_guard = state.ctx.lock().map_err(|_| "Failed to lock ctx")?;
+    let english_only = uses_model
+        && ctx_guard
+            .as_ref()
+            .map_or(false, |ctx| !ctx.is_multilingual());
+    if english_only && (language != "en" || translate) {
+        return Err("The selected model is English-only, pick a multilingual model".to_string());
+    }
+    Ok(DecodeConfig {
+        language,
+        translate,
+        options: decoding.unwrap_or_default(),
+    })
+}
+
+async fn download_tracked<R: Runtime>(app: &AppHandle<R>, state: &WhisperState, model: &models::ModelInfo) -> Result<PathBuf, String> {
+    let cancel = {
+        let mut downloads = state.downloads.lock().unwrap();
+        if downloads.contains_key(model.id) {
+            return Err(format!("Model {} is already downloading", model.id));
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        downloads.insert(model.id.to_string(), cancel.clone());
+        cancel
+    };
+    let result = models::download(app, model, &cancel).await;
+    state.downloads.lock().unwrap().remove(model.id);
+    result
+}
+
+#[derive(Clone, serde::Serialize)]
+struct StreamErrorPayload {
+    session: String,
+    error: String,
+}
+
+#[command]
+async fn list_input_devices() -> Result<Vec<String>, String> {
+    capture::list_input_devices()
+}
+
+#[command]
+async fn list_models<R: Runtime>(app: AppHandle<R>) -> Result<Vec<models::ModelEntry>, String> {
+    models::list(&app)
+}
+
+#[command]
+async fn download_model<R: Runtime>(app: AppHandle<R>, state: State<'_, WhisperState>, model: String) -> Result<(), String> {
+    let info = models::find(&model)?;
+    download_tracked(&app, &state, info).await?;
+    Ok(())
+}
+
+#[command]
+async fn cancel_download(state: State<'_, WhisperState>, model: Option<String>) -> Result<(), String> {
+    let downloads = state.downloads.lock().unwrap();
+    for (id, cancel) in downloads.iter() {
+        if model.as_deref().map_or(true, |m| m == id) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+    Ok(())
+}
+
+/// Copies a local `.bin` model, quantized ones included, into the app and registers it as `name`.
+#[command]
+async fn import_model<R: Runtime>(app: AppHandle<R>, path: String, name: String, sha256: Option<String>) -> Result<models::ModelEntry, String> {
+    let dir = models::whisper_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || models::import(&dir, Path::new(&path), &name, sha256.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+async fn delete_model<R: Runtime>(app: AppHandle<R>, state: State<'_, WhisperState>, model: String) -> Result<(), String> {
+    let dir = models::whisper_dir(&app)?;
+    let imported = models::find_imported(&dir, &model)?;
+    let info = match imported {
+        Some(_) => None,
+        None => Some(models::find(&model)?),
+    };
+    {
+        let mut ctx_guard = state.ctx.lock().map_err(|_| "Failed to lock ctx")?;
+        let mut slot_guard = state.model.lock().map_err(|_| "Failed to lock model")?;
+        if slot_guard.as_ref().map_or(false, |slot| slot.id == model) {
+            if state.is_busy() {
+                return Err("Model is in use".to_string());
+            }
+            *ctx_guard = None;
+            *slot_guard = None;
+        }
+    }
+    match info {
+        Some(info) => models::delete(&app, info),
+        None => models::delete_imported(&dir, &model),
+    }
+}
+
+#[command]
+async fn unload_model<R: Runtime>(app: AppHandle<R>, state: State<'_, WhisperState>) -> Result<(), String> {
+    if let Some(model) = unload_model_if(&state, None)? {
+        let _ = app.emit_all(
+            "whisper:model_unloaded",
+            UnloadedPayload {
+                model,
+                reason: UnloadReason::Manual,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Unloads the model after `minutes` without recording, None or 0 keeps it loaded.
+#[command]
+async fn set_idle_timeout(state: State<'_, WhisperState>, minutes: Option<u64>) -> Result<(), String> {
+    *state.idle_timeout.lock().unwrap() = minutes
+        .filter(|m| *m > 0)
+        .map(|m| Duration::from_secs(m * 60));
+    state.touch_model();
+    Ok(())
+}
+
+#[command]
+async fn model_status(state: State<'_, WhisperState>) -> Result<ModelStatus, String> {
+    let loaded = state
+        .ctx
+        .lock()
+        .map_err(|_| "Failed to lock ctx")?
+        .is_some();
+    let slot_guard = state.model.lock().map_err(|_| "Failed to lock model")?;
+    Ok(ModelStatus {
+        model: slot_guard.as_ref().map(|slot| slot.id.clone()),
+        loaded,
+        memory_mb: slot_guard
+            .as_ref()
+            .filter(|_| loaded)
+            .map(ModelSlot::approx_memory_mb),
+        last_used_ms: slot_guard.as_ref().map(ModelSlot::last_used_ms),
+        idle_timeout_minutes: state.idle_timeout.lock().unwrap().map(|t| t.as_secs() / 60),
+    })
+}
+
+/// Loads the model, downloading built-in ones first. Imported models have to be in place already.
+#[command]
+async fn ensure_dependencies<R: Runtime>(app: AppHandle<R>, state: State<'_, WhisperState>, model: Option<String>) -> Result<(), String> {
+    let model = model.as_deref().unwrap_or(models::DEFAULT_MODEL);
+    if models::find_imported(&models::whisper_dir(&app)?, model)?.is_some() {
+        return load_installed_model(&app, &state, model);
+    }
+    let info = models::find(model)?;
+    let model_path = download_tracked(&app, &state, info).await?;
+    load_model(&state, info.id, &model_path)
+}
+
+/// Options of `start_recording`, the first five are required.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StartOptions {
+    vad_enabled: bool,
+    silence_threshold_db: f32,
+    silence_duration_ms: u64,
+    min_chunk_duration_ms: u64,
+    /// Capture from `device_name`, otherwise audio comes from a remote source.
+    capture_local: bool,
+    model: Option<String>,
+    interim_interval_ms: Option<u64>,
+    language: Option<String>,
+    task: Option<String>,
+    vad_frame_ms: Option<u64>,
+    vad_attack_ms: Option<u64>,
+    vad_pre_roll_ms: Option<u64>,
+    /// Finals of this session that may wait for the worker.
+    queue_capacity: Option<usize>,
+    backlog_policy: Option<BacklogPolicy>,
+    device_name: Option<String>,
+    decoding: Option<DecodeOptions>,
+    filter: Option<FilterOptions>,
+    /// Session id, the default session when left out.
+    session: Option<String>,
+    segmentation: Option<Segmentation>,
+    level_interval_ms: Option<u64>,
+    preprocess: Option<PreprocessOptions>,
+    server: Option<ServerOptions>,
+    recorder: Option<RecorderOptions>,
+}
+
+#[command]
+async fn start_recording<R: Runtime>(app: AppHandle<R>, state: State<'_, WhisperState>, options: StartOptions) -> Result<(), String> {
+    let StartOptions {
+        vad_enabled,
+        silence_threshold_db,
+        silence_duration_ms,
+        min_chunk_duration_ms,
+        capture_local,
+        model,
+        interim_interval_ms,
+        language,
+        task,
+        vad_frame_ms,
+        vad_attack_ms,
+        vad_pre_roll_ms,
+        queue_capacity,
+        backlog_policy,
+        device_name,
+        decoding,
+        filter,
+        session,
+        segmentation,
+        level_interval_ms,
+        preprocess,
+        server,
+        recorder,
+    } = options;
+    let session_id = session.unwrap_or_else(|| DEFAULT_SESSION.to_string());
+    if state.session(&session_id).is_some() {
+        return Err(format!("Session {} is already recording", session_id));
+    }
+    // the capture callback would wait on the worker and the device would overrun
+    if capture_local && backlog_policy == Some(BacklogPolicy::Block) {
+        return Err("The block backlog policy cannot be used with a local capture device".to_string());
+    }
+    match &server {
+        Some(server) => server.validate()?,
+        None => prepare_model(&app, &state, model.as_deref())?,
+    }
+    let decode_config = build_decode_config(&state, language, task, decoding, server.is_none())?;
+    let recorder = match recorder.filter(RecorderOptions::is_enabled) {
+        Some(mut options) => {
+            let base = match options.dir.take() {
+                Some(dir) => dir,
+                None => models::whisper_dir(&app)?.join("recordings"),
+            };
+            let started = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let dir = base.join(format!("{}-{}", session_id, started.as_secs()));
+            std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            options.dir = Some(dir);
+            Some(options)
+        }
+        None => None,
+    };
+
+    let mut config_sample_rate = 16000;
+    let mut config_channels = 1;
+    let device_name = capture_local.then(|| device_name.unwrap_or_else(|| "default".to_string()));
+    let device_opt = if let Some(device_name) = &device_name {
+        let device = capture::find_input_device(device_name)?;
+        let config = device.default_input_config().map_err(|e| e.to_string())?;
+        config_sample_rate = config.sample_rate().0;
+        config_channels = config.channels();
+        Some((device, config))
+    } else {
+        None
+    };
+
+    let segmentation = segmentation.unwrap_or(if vad_enabled { Segmentation::Vad } else { Segmentation::Timer });
+    let defaults = VadConfig::default();
+    let session = Arc::new(Session::new(
+        session_id.clone(),
+        SessionConfig {
+            segmentation,
+            sample_rate: config_sample_rate,
+            channels: config_channels,
+            device: device_name,
+            preprocess: preprocess.unwrap_or_default(),
+            vad: VadConfig {
+                silence_threshold_db,
+                silence_duration_ms,
+                min_chunk_duration_ms,
+                frame_ms: vad_frame_ms.unwrap_or(defaults.frame_ms),
+                attack_ms: vad_attack_ms.unwrap_or(defaults.attack_ms),
+                pre_roll_ms: vad_pre_roll_ms.unwrap_or(defaults.pre_roll_ms),
+                max_utterance_ms: defaults.max_utterance_ms,
+            },
+            decode: decode_config,
+            filter: filter.unwrap_or_default(),
+            interim_interval_ms: interim_interval_ms.filter(|ms| *ms > 0),
+            queue_capacity: queue_capacity.unwrap_or(queue::DEFAULT_CAPACITY).max(1),
+            backlog_policy: backlog_policy.unwrap_or(BacklogPolicy::DropOldest),
+            level_interval_ms: Some(level_interval_ms.unwrap_or(DEFAULT_LEVEL_INTERVAL_MS)).filter(|ms| *ms > 0),
+            server,
+            recorder,
+            commands: app.state::<VoiceCommands>().inner().clone(),
+        },
+    ));
+
+    let (tx, rx) = channel();
+    *session.stop_sender.lock().unwrap() = Some(tx);
+    {
+        let mut sessions = state.sessions.lock().unwrap();
+        if sessions.contains_key(&session_id) {
+            return Err(format!("Session {} is already recording", session_id));
+        }
+        // checked under the sessions lock, which `benchmark` holds while it sets the flag
+        if state.benchmarking.load(Ordering::SeqCst) {
+            return Err("A benchmark is running, start recording once it is done".to_string());
+        }
+        sessions.insert(session_id.clone(), session.clone());
+    }
+    if let Some(queue) = session.queue.clone() {
+        let (handle, transcriber) = (app.clone(), state.transcriber());
+        thread::spawn(move || pipeline::inference_worker(handle, &queue, &transcriber));
+    }
+
+    if capture_local {
+        let (device, config) = device_opt.unwrap();
+        let queue = state.queue_for(&session);
+        let session_clone = session.clone();
+        let app_clone = app.clone();
+        let error_app = app.clone();
+        let (ready_tx, ready_rx) = channel();
+        thread::spawn(move || {
+            let stream = capture::build_input_stream(
+                &device,
+                &config,
+                move |data| pipeline::process_audio_chunk(&queue, &session_clone, &app_clone, data),
+                move |error| {
+                    let _ = error_app.emit_all(
+                        "whisper:stream_error",
+                        StreamErrorPayload {
+                            session: session_id.clone(),
+                            error,
+                        },
+                    );
+                },
+            );
+            // the stream has to live on this thread, only the outcome goes back
+            match stream.and_then(|stream| stream.play().map(|_| stream).map_err(|e| e.to_string())) {
+                Ok(_stream) => {
+                    let _ = ready_tx.send(Ok(()));
+                    let _ = rx.recv();
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                }
+            }
+        });
+        if let Err(e) = ready_rx
+            .recv()
+            .unwrap_or(Err("Capture thread exited".to_string()))
+        {
+            *session.is_recording.lock().unwrap() = false;
+            session.stop_sender.lock().unwrap().take();
+            state.sessions.lock().unwrap().remove(&session.id);
+            if let Some(queue) = &session.queue {
+                queue.close();
+            }
+            return Err(e);
+        }
+    } else {
+        thread::spawn(move || {
+            let _ = rx.recv();
+        });
+    }
+    Ok(())
+}
+
+#[derive(Clone, serde::Serialize)]
+struct FileProgressPayload {
+    path: String,
+    processed_ms: u64,
+    /// Not every container reports its length up front.
+    progress: Option<f64>,
+}
+
+/// Options of `transcribe_file`, all can be left out.
+#[derive(Default, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct TranscribeFileOptions {
+    format: Option<SubtitleFormat>,
+    model: Option<String>,
+    language: Option<String>,
+    task: Option<String>,
+    decoding: Option<DecodeOptions>,
+    filter: Option<FilterOptions>,
+}
+
+/// Captions an audio file with the loaded model and writes the result next to it, existing files are kept. Returns the output path.
+#[command]
+async fn transcribe_file<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, WhisperState>,
+    path: String,
+    options: Option<TranscribeFileOptions>,
+) -> Result<String, String> {
+    let TranscribeFileOptions {
+        format,
+        model,
+        language,
+        task,
+        decoding,
+        filter,
+    } = options.unwrap_or_default();
+    prepare_model(&app, &state, model.as_deref())?;
+    let decode_config = build_decode_config(&state, language, task, decoding, true)?;
+    let filter_options = filter.unwrap_or_default();
+    let format = format.unwrap_or_default();
+    let source = PathBuf::from(&path);
+
+    let cancel = {
+        let mut file_jobs = state.file_jobs.lock().unwrap();
+        if file_jobs.contains_key(&path) {
+            return Err(format!("{} is already being transcribed", path));
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        file_jobs.insert(path.clone(), cancel.clone());
+        cancel
+    };
+
+    let transcriber = state.transcriber();
+    let job_path = path.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let audio = file::open(&source)?;
+        let duration_ms = audio.duration_ms;
+        let segments = file::transcribe(
+            audio,
+            &cancel,
+            |audio, committed_text| {
+                let mut transcript = transcriber.transcribe(&decode_config, &decode_config.prompt(committed_text), audio)?;
+                filter::apply(&filter_options, &mut transcript);
+                Ok(transcript)
+            },
+            |processed_ms| {
+                let progress = duration_ms
+                    .filter(|d| *d > 0)
+                    .map(|d| (processed_ms as f64 / d as f64).min(1.0));
+                let _ = app.emit_all(
+                    "whisper:file_progress",
+                    FileProgressPayload {
+                        path: job_path.clone(),
+                        processed_ms,
+                        progress,
+                    },
+                );
+            },
+        )?;
+        let output = subtitles::write_next_to(&source, format, &subtitles::render(format, &segments))?;
+        Ok(output.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+    state.file_jobs.lock().unwrap().remove(&path);
+    state.touch_model();
+    result
+}
+
+/// Times every installed model, or the ones in `models`, on the bundled sample or the file at `path`.
+/// Pass the live decoding options so beam size and threads match what recording uses.
+#[command]
+async fn benchmark<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, WhisperState>,
+    models: Option<Vec<String>>,
+    path: Option<String>,
+    decoding: Option<DecodeOptions>,
+) -> Result<Vec<BenchmarkResult>, String> {
+    // a live session would compete for the CPU and skew the numbers
+    {
+        let sessions = state.sessions.lock().unwrap();
+        if !sessions.is_empty() || !state.file_jobs.lock().unwrap().is_empty() {
+            return Err("Stop recording before running the benchmark".to_string());
+        }
+        if state.benchmarking.swap(true, Ordering::SeqCst) {
+            return Err("A benchmark is already running".to_string());
+        }
+    }
+    let result = run_benchmark(app, models, path, decoding).await;
+    state.benchmarking.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn run_benchmark<R: Runtime>(
+    app: AppHandle<R>,
+    models: Option<Vec<String>>,
+    path: Option<String>,
+    decoding: Option<DecodeOptions>,
+) -> Result<Vec<BenchmarkResult>, String> {
+    let sample = match path {
+        Some(path) => PathBuf::from(path),
+        None => app
+            .path_resolver()
+            .resolve_resource(benchmark::SAMPLE)
+            .ok_or("Benchmark sample is missing")?,
+    };
+    let dir = models::whisper_dir(&app)?;
+    let installed: Vec<(String, PathBuf)> = models::list(&app)?
+        .into_iter()
+        .filter(|m| m.installed && models.as_ref().map_or(true, |ids| ids.contains(&m.id)))
+        .map(|m| (m.id, dir.join(m.file)))
+        .collect();
+    if installed.is_empty() {
+        return Err("No installed models to benchmark".to_string());
+    }
+    // English, so English-only models can run it too
+    let decode_config = DecodeConfig {
+        options: decoding.unwrap_or_default(),
+        ..DecodeConfig::default()
+    };
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<Vec<BenchmarkResult>, String> {
+        let audio = file::open(&sample)?.into_whisper_input();
+        let total = installed.len();
+        let mut results = Vec::with_capacity(total);
+        for (index, (model, path)) in installed.into_iter().enumerate() {
+            let _ = app.emit_all(
+                "whisper:benchmark_progress",
+                BenchmarkProgress {
+                    model: model.clone(),
+                    index,
+                    total,
+                },
+            );
+            results.push(benchmark::run(&model, &path, &decode_config, &audio));
+        }
+        Ok(results)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+async fn cancel_transcribe_file(state: State<'_, WhisperState>, path: Option<String>) -> Result<(), String> {
+    let file_jobs = state.file_jobs.lock().unwrap();
+    for (job_path, cancel) in file_jobs.iter() {
+        if path.as_deref().map_or(true, |p| p == job_path) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+    Ok(())
+}
+
+#[command]
+async fn feed_audio_chunk<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, WhisperState>,
+    chunk: Vec<f32>,
+    session: Option<String>,
+) -> Result<(), String> {
+    let session_id = session.as_deref().unwrap_or(DEFAULT_SESSION);
+    let session = state
+        .session(session_id)
+        .ok_or(format!("Session {} is not recording", session_id))?;
+    pipeline::process_audio_chunk(&state.queue_for(&session), &session, &app, chunk);
+    Ok(())
+}
+
+/// Push-to-talk: starts buffering a new utterance, audio before this is discarded.
+#[command]
+async fn begin_utterance(state: State<'_, WhisperState>, session: Option<String>) -> Result<(), String> {
+    let session_id = session.as_deref().unwrap_or(DEFAULT_SESSION);
+    let session = state
+        .session(session_id)
+        .ok_or(format!("Session {} is not recording", session_id))?;
+    pipeline::begin_utterance(&session)
+}
+
+/// Push-to-talk: transcribes exactly the audio buffered since `begin_utterance` and returns the result.
+#[command]
+async fn end_utterance<R: Runtime>(app: AppHandle<R>, state: State<'_, WhisperState>, session: Option<String>) -> Result<Transcript, String> {
+    let session_id = session.as_deref().unwrap_or(DEFAULT_SESSION);
+    let session = state
+        .session(session_id)
+        .ok_or(format!("Session {} is not recording", session_id))?;
+    let reply_rx = pipeline::end_utterance(&state.queue_for(&session), &session, &app)?;
+    reply_rx
+        .await
+        .map_err(|_| "Utterance was dropped from the backlog".to_string())?
+}
+
+/// Writes the session's replay buffer to `path`, or into its recording folder. Returns the file written.
+#[command]
+async fn save_replay(state: State<'_, WhisperState>, session: Option<String>, path: Option<String>) -> Result<String, String> {
+    let session_id = session.as_deref().unwrap_or(DEFAULT_SESSION);
+    let session = state.session(session_id).ok_or("Not recording")?;
+    let path = tauri::async_runtime::spawn_blocking(move || {
+        let recorder = session
+            .recorder
+            .as_ref()
+            .ok_or(format!("Session {} has no recorder", session.id))?;
+        recorder.save_replay(path.map(PathBuf::from))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Transcribes whatever was still buffered and returns its text once every earlier
+/// utterance of the session is out, then emits `whisper:stopped`.
+#[command]
+async fn stop_recording<R: Runtime>(app: AppHandle<R>, state: State<'_, WhisperState>, session: Option<String>) -> Result<String, String> {
+    let session_id = session.as_deref().unwrap_or(DEFAULT_SESSION);
+    let Some(session) = state.sessions.lock().unwrap().remove(session_id) else {
+        return Err("Not recording".to_string());
+    };
+    let queue = state.queue_for(&session);
+    let reply_rx = pipeline::finish_session(&queue, &session, &app);
+    if let Some(tx) = session.stop_sender.lock().unwrap().take() {
+        let _ = tx.send(());
+    }
+
+    let drained = session.clone();
+    tauri::async_runtime::spawn_blocking(move || queue.drain(&drained))
+        .await
+        .map_err(|e| e.to_string())?;
+    // lets the worker of a server session exit
+    if let Some(queue) = &session.queue {
+        queue.close();
+    }
+    let text = match reply_rx {
+        // a dropped backlog entry or a failed inference leaves nothing to return
+        Some(reply_rx) => reply_rx
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .map(|transcript| transcript.text)
+            .unwrap_or_default(),
+        None => String::new(),
+    };
+    state.touch_model();
+    let _ = app.emit_all("whisper:stopped", session.stats(text.clone()));
+    Ok(text)
+}
+
+/// Collects `frames` frames from a capture stream and downmixes them.
+fn record(rx: &Receiver<Vec<f32>>, frames: usize, channels: u16) -> Result<Vec<f32>, String> {
+    let mut samples = Vec::with_capacity(frames * channels as usize);
+    while samples.len() < frames * channels as usize {
+        let data = rx
+            .recv_timeout(Duration::from_secs(2))
+            .map_err(|_| "Input device stopped delivering audio".to_string())?;
+        samples.extend_from_slice(&data);
+    }
+    samples.truncate(frames * channels as usize);
+    Ok(dsp::downmix(&samples, channels))
+}
+
+/// Records the quiet room, then the user talking, and suggests VAD settings from the two.
+/// `whisper:calibration` carries "noise" and "speech" so the UI knows what to ask for.
+/// Pass the same `preprocess` as `start_recording` so the levels match what the VAD will see.
+#[command]
+async fn calibrate_vad<R: Runtime>(
+    app: AppHandle<R>,
+    device_name: Option<String>,
+    noise_secs: Option<u64>,
+    speech_secs: Option<u64>,
+    preprocess: Option<PreprocessOptions>,
+) -> Result<Calibration, String> {
+    let device = capture::find_input_device(device_name.as_deref().unwrap_or("default"))?;
+    let config = device.default_input_config().map_err(|e| e.to_string())?;
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels();
+    let noise_frames = (sample_rate as u64 * noise_secs.unwrap_or(3).max(1)) as usize;
+    let speech_frames = (sample_rate as u64 * speech_secs.unwrap_or(3).max(1)) as usize;
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<Calibration, String> {
+        let (tx, rx) = channel();
+        let stream = capture::build_input_stream(
+            &device,
+            &config,
+            move |data| {
+                let _ = tx.send(data);
+            },
+            |_| {},
+        )?;
+        stream.play().map_err(|e| e.to_string())?;
+        let _ = app.emit_all("whisper:calibration", "noise");
+        let noise = record(&rx, noise_frames, channels)?;
+        let _ = app.emit_all("whisper:calibration", "speech");
+        let speech = record(&rx, speech_frames, channels)?;
+        drop(stream);
+        // one chain over both recordings, noise suppression needs the room to learn from
+        match Preprocessor::new(&preprocess.unwrap_or_default(), sample_rate, 1) {
+            Some(mut preprocessor) => {
+                let noise = preprocessor.process(&noise);
+                let speech = preprocessor.process(&speech);
+                level::calibrate(&noise, &speech, sample_rate)
+            }
+            None => level::calibrate(&noise, &speech, sample_rate),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+async fn list_sessions(state: State<'_, WhisperState>) -> Result<Vec<SessionInfo>, String> {
+    let sessions = state.sessions.lock().unwrap();
+    let mut infos: Vec<SessionInfo> = sessions.values().map(|session| session.info()).collect();
+    infos.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(infos)
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new("whisper")
+        .setup(|app| {
+            let state = WhisperState::new();
+            app.manage(state.clone());
+            let handle = app.app_handle();
+            let transcriber = state.transcriber();
+            let idle_handle = handle.clone();
+            let idle_state = state.clone();
+            thread::spawn(move || pipeline::inference_worker(handle, &state.queue, &transcriber));
+            thread::spawn(move || idle_watcher(idle_handle, idle_state));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            ensure_dependencies,
+            list_models,
+            download_model,
+            cancel_download,
+            delete_model,
+            import_model,
+            unload_model,
+            set_idle_timeout,
+            model_status,
+            list_input_devices,
+            calibrate_vad,
+            start_recording,
+            stop_recording,
+            list_sessions,
+            feed_audio_chunk,
+            begin_utterance,
+            end_utterance,
+            save_replay,
+            transcribe_file,
+            cancel_transcribe_file,
+            benchmark
+        ])
+        .build()
+}
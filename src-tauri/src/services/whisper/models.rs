@@ -0,0 +1,465 @@
+use futures::StreamExt;
+use reqwest::{header::RANGE, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tauri::{AppHandle, Manager, Runtime};
+
+const MODEL_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+pub const DEFAULT_MODEL: &str = "base.en";
+const IMPORTED_FILE: &str = "imported.json";
+/// ggml magic, the first u32 of every whisper.cpp model. Stored little endian, so the file starts with the bytes `lmgg`.
+const GGML_MAGIC: u32 = 0x6767_6d6c;
+/// English-only models have one token less than the multilingual ones.
+const ENGLISH_VOCAB: i32 = 51864;
+
+pub struct ModelInfo {
+    pub id: &'static str,
+    pub file: &'static str,
+    pub sha256: &'static str,
+    pub size_mb: u32,
+    pub multilingual: bool,
+}
+
+impl ModelInfo {
+    pub fn url(&self) -> String {
+        format!("{}/{}", MODEL_BASE_URL, self.file)
+    }
+}
+
+pub const MODELS: &[ModelInfo] = &[
+    ModelInfo {
+        id: "tiny",
+        file: "ggml-tiny.bin",
+        sha256: "be07e048e1e599ad46341c8d2a135645097a538221678b7acdd1b1919c6e1b21",
+        size_mb: 75,
+        multilingual: true,
+    },
+    ModelInfo {
+        id: "tiny.en",
+        file: "ggml-tiny.en.bin",
+        sha256: "921e4cf8686fdd993dcd081a5da5b6c365bfde1162e72b08d75ac75289920b1f",
+        size_mb: 75,
+        multilingual: false,
+    },
+    ModelInfo {
+        id: "base",
+        file: "ggml-base.bin",
+        sha256: "60ed5bc3dd14eea856493d334349b405782ddcaf0028d4b5df4088345fba2efe",
+        size_mb: 142,
+        multilingual: true,
+    },
+    ModelInfo {
+        id: "base.en",
+        file: "ggml-base.en.bin",
+        sha256: "a03779c86df3323075f5e796cb2ce5029f00ec8869eee3fdfb897afe36c6d002",
+        size_mb: 142,
+        multilingual: false,
+    },
+    ModelInfo {
+        id: "small",
+        file: "ggml-small.bin",
+        sha256: "1be3a9b2063867b937e64e2ec7483364a79917e157fa98c5d94b5c1fffea987b",
+        size_mb: 466,
+        multilingual: true,
+    },
+    ModelInfo {
+        id: "small.en",
+        file: "ggml-small.en.bin",
+        sha256: "c6138d6d58ecc8322097e0f987c32f1be8bb0a18532a3f88f734d1bbf9c41e5d",
+        size_mb: 466,
+        multilingual: false,
+    },
+    ModelInfo {
+        id: "medium",
+        file: "ggml-medium.bin",
+        sha256: "6c14d5adee5f86394037b4e4e8b59f1673b6cee10e3cf0b11bbdbee79c156208",
+        size_mb: 1463,
+        multilingual: true,
+    },
+    ModelInfo {
+        id: "medium.en",
+        file: "ggml-medium.en.bin",
+        sha256: "cc37e93478338ec7700281a7ac30a10128929eb8f427dda2e865faa8f6da4356",
+        size_mb: 1463,
+        multilingual: false,
+    },
+];
+
+pub fn find(id: &str) -> Result<&'static ModelInfo, String> {
+    MODELS
+        .iter()
+        .find(|m| m.id == id)
+        .ok_or(format!("Unknown model: {}", id))
+}
+
+/// A model the user brought in with `import_model`, kept in `imported.json` next to the files.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ImportedModel {
+    /// Display name, used in place of a model id.
+    pub id: String,
+    pub file: String,
+    pub sha256: String,
+    pub size_mb: u32,
+    pub multilingual: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ModelEntry {
+    pub id: String,
+    pub file: String,
+    pub size_mb: u32,
+    pub multilingual: bool,
+    pub installed: bool,
+    pub imported: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct ProgressPayload {
+    file: String,
+    progress: f64,
+}
+
+pub fn whisper_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    let app_data_dir = app
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Failed to get app data directory")?;
+    let whisper_dir = app_data_dir.join("whisper");
+    if !whisper_dir.exists() {
+        fs::create_dir_all(&whisper_dir).map_err(|e| e.to_string())?;
+    }
+    Ok(whisper_dir)
+}
+
+pub fn list<R: Runtime>(app: &AppHandle<R>) -> Result<Vec<ModelEntry>, String> {
+    let dir = whisper_dir(app)?;
+    Ok(MODELS
+        .iter()
+        .map(|m| ModelEntry {
+            id: m.id.to_string(),
+            file: m.file.to_string(),
+            size_mb: m.size_mb,
+            multilingual: m.multilingual,
+            installed: dir.join(m.file).exists(),
+            imported: false,
+        })
+        .chain(read_imported(&dir)?.into_iter().map(|m| ModelEntry {
+            installed: dir.join(&m.file).exists(),
+            id: m.id,
+            file: m.file,
+            size_mb: m.size_mb,
+            multilingual: m.multilingual,
+            imported: true,
+        }))
+        .collect())
+}
+
+/// Path of an installed model, built in or imported.
+pub fn installed_path<R: Runtime>(app: &AppHandle<R>, id: &str) -> Result<PathBuf, String> {
+    let dir = whisper_dir(app)?;
+    let file = match find_imported(&dir, id)? {
+        Some(imported) => imported.file,
+        None => find(id)?.file.to_string(),
+    };
+    let path = dir.join(file);
+    if !path.exists() {
+        return Err(format!("Model {} is not installed", id));
+    }
+    Ok(path)
+}
+
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let count = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn check_hash(hash_hex: &str, expected_hash: &str) -> Result<(), String> {
+    if !hash_hex.eq_ignore_ascii_case(expected_hash) {
+        return Err(format!("Hash mismatch! Expected {}, got {}", expected_hash, hash_hex));
+    }
+    Ok(())
+}
+
+pub fn verify_file(path: &Path, expected_hash: &str) -> Result<(), String> {
+    check_hash(&hash_file(path)?, expected_hash)
+}
+
+#[derive(Clone, Serialize)]
+struct DownloadErrorPayload {
+    file: String,
+    error: String,
+}
+
+/// Downloads the model if it is not present yet and returns its path.
+/// Data goes to a `.part` file first, which is resumed on the next attempt and
+/// only renamed into place once its hash matches.
+pub async fn download<R: Runtime>(app: &AppHandle<R>, model: &ModelInfo, cancel: &AtomicBool) -> Result<PathBuf, String> {
+    let model_path = whisper_dir(app)?.join(model.file);
+    if model_path.exists() {
+        return Ok(model_path);
+    }
+    let part_path = model_path.with_extension("bin.part");
+
+    let result = match download_part(app, model, &part_path, cancel).await {
+        Ok(()) => verify_file(&part_path, model.sha256).map_err(|e| {
+            fs::remove_file(&part_path).ok();
+            e
+        }),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        let _ = app.emit_all(
+            "whisper:download_error",
+            DownloadErrorPayload {
+                file: model.file.to_string(),
+                error: e.clone(),
+            },
+        );
+        return Err(e);
+    }
+    fs::rename(&part_path, &model_path).map_err(|e| e.to_string())?;
+    Ok(model_path)
+}
+
+async fn download_part<R: Runtime>(app: &AppHandle<R>, model: &ModelInfo, part_path: &Path, cancel: &AtomicBool) -> Result<(), String> {
+    let offset = fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = reqwest::Client::new().get(model.url());
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Model download failed: {}", e))?;
+
+    // the part file already holds the whole model, let the hash check decide
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(());
+    }
+    let response = response
+        .error_for_status()
+        .map_err(|e| format!("Model download failed: {}", e))?;
+
+    // servers that ignore the range header send the whole file again
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { offset } else { 0 };
+    let total = response
+        .content_length()
+        .map(|l| l + downloaded)
+        .unwrap_or(0);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part_path)
+        .map_err(|e| e.to_string())?;
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Download cancelled".to_string());
+        }
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        downloaded += chunk.len() as u64;
+        if total > 0 {
+            let _ = app.emit_all(
+                "whisper:download_progress",
+                ProgressPayload {
+                    file: model.file.to_string(),
+                    progress: (downloaded as f64 / total as f64) * 100.0,
+                },
+            );
+        }
+    }
+    file.flush().map_err(|e| e.to_string())
+}
+
+pub fn delete<R: Runtime>(app: &AppHandle<R>, model: &ModelInfo) -> Result<(), String> {
+    let model_path = whisper_dir(app)?.join(model.file);
+    if model_path.exists() {
+        fs::remove_file(&model_path).map_err(|e| e.to_string())?;
+    }
+    fs::remove_file(model_path.with_extension("bin.part")).ok();
+    Ok(())
+}
+
+fn read_imported(dir: &Path) -> Result<Vec<ImportedModel>, String> {
+    let path = dir.join(IMPORTED_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to read {}: {}", IMPORTED_FILE, e))
+}
+
+fn write_imported(dir: &Path, models: &[ImportedModel]) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(models).map_err(|e| e.to_string())?;
+    fs::write(dir.join(IMPORTED_FILE), data).map_err(|e| e.to_string())
+}
+
+pub fn find_imported(dir: &Path, id: &str) -> Result<Option<ImportedModel>, String> {
+    Ok(read_imported(dir)?.into_iter().find(|m| m.id == id))
+}
+
+/// Checks the ggml header and tells whether the model is multilingual. Quantized files share the header.
+fn read_header(path: &Path) -> Result<bool, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open: {}", e))?;
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)
+        .map_err(|_| "Not a whisper.cpp model, the file is too short".to_string())?;
+    if u32::from_le_bytes([header[0], header[1], header[2], header[3]]) != GGML_MAGIC {
+        return Err("Not a whisper.cpp model, expected a ggml .bin file".to_string());
+    }
+    let n_vocab = i32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    Ok(n_vocab > ENGLISH_VOCAB)
+}
+
+/// Copies a local model into the whisper directory and registers it under `name`.
+/// `sha256` is checked first when given, the file's own hash is stored either way.
+pub fn import(dir: &Path, source: &Path, name: &str, sha256: Option<&str>) -> Result<ModelEntry, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Model name is empty".to_string());
+    }
+    let mut imported = read_imported(dir)?;
+    if find(name).is_ok() || imported.iter().any(|m| m.id == name) {
+        return Err(format!("Model {} already exists", name));
+    }
+    let multilingual = read_header(source)?;
+    let hash = hash_file(source)?;
+    if let Some(expected) = sha256 {
+        check_hash(&hash, expected)?;
+    }
+    if let Some(existing) = imported.iter().find(|m| m.sha256 == hash) {
+        return Err(format!("This model is already imported as {}", existing.id));
+    }
+
+    let file = format!("ggml-imported-{}.bin", &hash[..16]);
+    let model_path = dir.join(&file);
+    let part_path = model_path.with_extension("bin.part");
+    let size = fs::metadata(source).map_err(|e| e.to_string())?.len();
+    let copied = File::open(source).and_then(|mut input| {
+        let mut output = BufWriter::new(File::create(&part_path)?);
+        std::io::copy(&mut input, &mut output)?;
+        output.flush()
+    });
+    if let Err(e) = copied {
+        fs::remove_file(&part_path).ok();
+        return Err(format!("Failed to copy model: {}", e));
+    }
+    fs::rename(&part_path, &model_path).map_err(|e| e.to_string())?;
+
+    let model = ImportedModel {
+        id: name.to_string(),
+        file,
+        sha256: hash,
+        size_mb: (size / (1024 * 1024)) as u32,
+        multilingual,
+    };
+    imported.push(model.clone());
+    write_imported(dir, &imported)?;
+    Ok(ModelEntry {
+        id: model.id,
+        file: model.file,
+        size_mb: model.size_mb,
+        multilingual,
+        installed: true,
+        imported: true,
+    })
+}
+
+pub fn delete_imported(dir: &Path, id: &str) -> Result<(), String> {
+    let mut imported = read_imported(dir)?;
+    let Some(index) = imported.iter().position(|m| m.id == id) else {
+        return Err(format!("Unknown model: {}", id));
+    };
+    let model = imported.remove(index);
+    let model_path = dir.join(&model.file);
+    if model_path.exists() {
+        fs::remove_file(&model_path).map_err(|e| e.to_string())?;
+    }
+    write_imported(dir, &imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whisper-models-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Just the header and some weights, enough for the checks import does.
+    fn fake_model(path: &Path, n_vocab: i32, filler: u8) {
+        let mut data = GGML_MAGIC.to_le_bytes().to_vec();
+        data.extend(n_vocab.to_le_bytes());
+        data.extend([filler; 1024]);
+        fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn imports_and_lists_under_display_name() {
+        let dir = scratch_dir("import");
+        let source = dir.join("ggml-large-v3-q5_0.bin");
+        fake_model(&source, 51866, 1);
+        let models_dir = dir.join("whisper");
+        fs::create_dir_all(&models_dir).unwrap();
+
+        let entry = import(&models_dir, &source, " Large v3 q5 ", None).unwrap();
+        assert_eq!(entry.id, "Large v3 q5");
+        assert!(entry.multilingual);
+        let imported = find_imported(&models_dir, "Large v3 q5").unwrap().unwrap();
+        assert_eq!(imported.sha256, hash_file(&source).unwrap());
+        assert_eq!(fs::read(models_dir.join(&imported.file)).unwrap(), fs::read(&source).unwrap());
+
+        delete_imported(&models_dir, "Large v3 q5").unwrap();
+        assert!(find_imported(&models_dir, "Large v3 q5").unwrap().is_none());
+        assert!(!models_dir.join(&imported.file).exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rejects_duplicates_and_bad_files() {
+        let dir = scratch_dir("reject");
+        let source = dir.join("ggml-base.en-q8_0.bin");
+        fake_model(&source, ENGLISH_VOCAB, 2);
+        let not_a_model = dir.join("notes.bin");
+        fs::write(&not_a_model, b"definitely not ggml").unwrap();
+
+        let entry = import(&dir, &source, "base q8", None).unwrap();
+        assert!(!entry.multilingual);
+        // same name, a built-in id, and the same file again under a new name
+        assert!(import(&dir, &source, "base q8", None).is_err());
+        assert!(import(&dir, &source, "base.en", None).is_err());
+        assert!(import(&dir, &source, "another", None).is_err());
+        assert!(import(&dir, &not_a_model, "notes", None).is_err());
+
+        let other = dir.join("other.bin");
+        fake_model(&other, ENGLISH_VOCAB, 3);
+        assert!(import(&dir, &other, "other", Some(&"0".repeat(64))).is_err());
+        let hash = hash_file(&other).unwrap();
+        assert!(import(&dir, &other, "other", Some(&hash.to_uppercase())).is_ok());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,411 @@
+use serde::Serialize;
+use std::{sync::Arc, time::Instant};
+use tauri::{AppHandle, Manager, Runtime};
+use tokio::sync::oneshot;
+
+use crate::services::voice_commands::CommandSource;
+
+use super::{
+    dsp,
+    filter::{self, RejectedPayload},
+    level::LevelPayload,
+    metrics::{self, MetricsPayload},
+    queue::{InferenceQueue, Job, JobKind},
+    recorder::{RecorderErrorPayload, UtteranceRecord},
+    session::{Segmentation, Session},
+    transcriber::Transcriber,
+    transcript::Transcript,
+    vad::VadEvent,
+};
+
+/// Length of each utterance in timer segmentation.
+const CHUNK_DURATION_SECS: u64 = 5;
+const MIN_INTERIM_DURATION_MS: u64 = 500;
+/// Anything shorter is not worth a run of the model, 0.2 s at 16 kHz.
+const MIN_FINAL_SAMPLES: usize = 3200;
+
+pub type Reply = oneshot::Sender<Result<Transcript, String>>;
+
+/// Payload of `whisper:error`, a final whose inference failed. Its audio is gone.
+#[derive(Clone, Serialize)]
+pub struct InferenceErrorPayload {
+    pub session: String,
+    pub sequence: u64,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub error: String,
+}
+
+/// Where the pipeline sends its events, the app in production and a recorder in tests.
+pub trait EventSink {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S);
+}
+
+impl<R: Runtime> EventSink for AppHandle<R> {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        let _ = self.emit_all(event, payload);
+    }
+}
+
+fn buffered_ms(session: &Session) -> u64 {
+    let frames = session.audio_buffer.lock().unwrap().len() / session.config.channels as usize;
+    frames as u64 * 1000 / session.config.sample_rate as u64
+}
+
+/// Feeds interleaved audio in the session's format through preprocessing and segmentation.
+pub fn process_audio_chunk<E: EventSink>(queue: &InferenceQueue, session: &Arc<Session>, events: &E, data: Vec<f32>) {
+    // held until the chunk is handled, so stop_recording finds every sample in the buffer
+    let recording = session.is_recording.lock().unwrap();
+    if !*recording {
+        return;
+    }
+
+    let channels = session.config.channels;
+    *session.received_frames.lock().unwrap() += (data.len() / channels as usize) as u64;
+    let data = match session.preprocessor.lock().unwrap().as_mut() {
+        Some(preprocessor) => preprocessor.process(&data),
+        None => data,
+    };
+    if let Some(recorder) = &session.recorder {
+        recorder.push_replay(&data, channels);
+    }
+
+    match session.config.segmentation {
+        Segmentation::Vad => {
+            let vad_events = session.vad.lock().unwrap().push(&data);
+            for event in vad_events {
+                match event {
+                    VadEvent::Start => session.audio_buffer.lock().unwrap().clear(),
+                    VadEvent::Audio(audio) => session
+                        .audio_buffer
+                        .lock()
+                        .unwrap()
+                        .extend_from_slice(&audio),
+                    VadEvent::End => flush_utterance(queue, session, events, None),
+                    // too little voiced time, most likely a click or a bump
+                    VadEvent::Cancel => session.audio_buffer.lock().unwrap().clear(),
+                }
+            }
+        }
+        Segmentation::Timer => {
+            session
+                .audio_buffer
+                .lock()
+                .unwrap()
+                .extend_from_slice(&data);
+            if buffered_ms(session) >= CHUNK_DURATION_SECS * 1000 {
+                flush_utterance(queue, session, events, None);
+            }
+        }
+        Segmentation::Manual => {
+            // the flag is held while appending so end_utterance cannot cut in between
+            let open = session.utterance_open.lock().unwrap();
+            if *open {
+                session
+                    .audio_buffer
+                    .lock()
+                    .unwrap()
+                    .extend_from_slice(&data);
+                // a held key is cut like long speech, the rest goes to `end_utterance`
+                if buffered_ms(session) >= session.config.vad.max_utterance_ms {
+                    flush_utterance(queue, session, events, None);
+                }
+            }
+        }
+    }
+    let level = session
+        .level_meter
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|meter| meter.push(&data));
+    if let Some((rms_db, peak_db)) = level {
+        events.emit(
+            "whisper:level",
+            LevelPayload {
+                session: session.id.clone(),
+                rms_db,
+                peak_db,
+                speech: session.vad.lock().unwrap().in_speech(),
+            },
+        );
+    }
+    process_interim(queue, session);
+}
+
+/// Hands the buffered utterance to inference and emits `whisper:final` once it is transcribed.
+/// `reply` also gets the result, or an empty transcript when the utterance was too short.
+pub fn flush_utterance<E: EventSink>(queue: &InferenceQueue, session: &Arc<Session>, events: &E, reply: Option<Reply>) {
+    let sample_rate = session.config.sample_rate;
+    let channels = session.config.channels;
+    let mut buf = session.audio_buffer.lock().unwrap();
+    let chunk = buf.clone();
+    buf.clear();
+    drop(buf);
+
+    // anything still in flight belongs to the utterance that just ended
+    let mut utterance_id = session.utterance_id.lock().unwrap();
+    let sequence = *utterance_id;
+    *utterance_id += 1;
+    session.interim_tracker.lock().unwrap().reset();
+    drop(utterance_id);
+
+    let end_frame = *session.received_frames.lock().unwrap();
+    let start_frame = end_frame.saturating_sub((chunk.len() / channels as usize) as u64);
+    *session.last_interim_frame.lock().unwrap() = end_frame;
+    let start_ms = start_frame * 1000 / sample_rate as u64;
+    let end_ms = end_frame * 1000 / sample_rate as u64;
+    if chunk.len() < MIN_FINAL_SAMPLES {
+        if let Some(reply) = reply {
+            let mut transcript = Transcript::new(Vec::new(), None, None).at(sequence, start_ms, end_ms);
+            transcript.session = session.id.clone();
+            let _ = reply.send(Ok(transcript));
+        }
+        return;
+    }
+    let job = Job {
+        kind: JobKind::Final { sequence, start_ms, end_ms },
+        session: session.clone(),
+        replies: reply.into_iter().collect(),
+        audio: chunk,
+        sample_rate,
+        channels,
+        queued_at: Instant::now(),
+    };
+    if let Some(backlog) = queue.push_final(job) {
+        events.emit("whisper:backlog", backlog);
+    }
+}
+
+/// Re-transcribes the growing utterance so captions can update before the final result.
+/// The interval is measured in received audio, so it holds for audio fed faster than real time.
+/// Each window is the whole utterance so far, `max_utterance_ms` keeps that bounded.
+fn process_interim(queue: &InferenceQueue, session: &Arc<Session>) {
+    let Some(interval_ms) = session.config.interim_interval_ms else {
+        return;
+    };
+    let listening = match session.config.segmentation {
+        Segmentation::Vad => session.vad.lock().unwrap().in_speech(),
+        Segmentation::Timer => true,
+        Segmentation::Manual => *session.utterance_open.lock().unwrap(),
+    };
+    if !listening {
+        return;
+    }
+    let sample_rate = session.config.sample_rate;
+    let received = *session.received_frames.lock().unwrap();
+    let mut last_interim = session.last_interim_frame.lock().unwrap();
+    if (received - *last_interim) * 1000 < interval_ms * sample_rate as u64 {
+        return;
+    }
+    if buffered_ms(session) < MIN_INTERIM_DURATION_MS {
+        return;
+    }
+    *last_interim = received;
+    drop(last_interim);
+
+    let utterance_id = *session.utterance_id.lock().unwrap();
+    queue.push_interim(Job {
+        kind: JobKind::Interim { utterance_id },
+        session: session.clone(),
+        replies: Vec::new(),
+        audio: session.audio_buffer.lock().unwrap().clone(),
+        sample_rate,
+        channels: session.config.channels,
+        queued_at: Instant::now(),
+    });
+}
+
+/// Push-to-talk: starts buffering a new utterance, audio before this is discarded.
+pub fn begin_utterance(session: &Session) -> Result<(), String> {
+    if session.config.segmentation != Segmentation::Manual {
+        return Err(format!("Session {} is not in manual segmentation mode", session.id));
+    }
+    let mut open = session.utterance_open.lock().unwrap();
+    if !*open {
+        session.audio_buffer.lock().unwrap().clear();
+        *session.last_interim_frame.lock().unwrap() = *session.received_frames.lock().unwrap();
+        *open = true;
+    }
+    Ok(())
+}
+
+/// Push-to-talk: queues exactly the audio buffered since `begin_utterance`, the receiver gets its result.
+pub fn end_utterance<E: EventSink>(
+    queue: &InferenceQueue,
+    session: &Arc<Session>,
+    events: &E,
+) -> Result<oneshot::Receiver<Result<Transcript, String>>, String> {
+    let mut open = session.utterance_open.lock().unwrap();
+    if !*open {
+        return Err(format!("Session {} has no open utterance", session.id));
+    }
+    *open = false;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    flush_utterance(queue, session, events, Some(reply_tx));
+    Ok(reply_rx)
+}
+
+/// Stops taking audio and queues what is still buffered when it is long enough to be speech.
+/// The receiver gets that last result, None when there was nothing to transcribe.
+pub fn finish_session<E: EventSink>(
+    queue: &InferenceQueue,
+    session: &Arc<Session>,
+    events: &E,
+) -> Option<oneshot::Receiver<Result<Transcript, String>>> {
+    // waits for a chunk that is still being appended
+    *session.is_recording.lock().unwrap() = false;
+    *session.utterance_open.lock().unwrap() = false;
+    if buffered_ms(session) < session.config.vad.min_chunk_duration_ms {
+        session.audio_buffer.lock().unwrap().clear();
+        return None;
+    }
+    let (reply_tx, reply_rx) = oneshot::channel();
+    flush_utterance(queue, session, events, Some(reply_tx));
+    Some(reply_rx)
+}
+
+fn transcribe_job<T: Transcriber + ?Sized>(transcriber: &T, session: &Session, resampled: &[f32]) -> Result<Transcript, String> {
+    let decode_config = &session.config.decode;
+    let prompt = decode_config.prompt(&session.previous_text.lock().unwrap());
+    match &session.server {
+        Some(server) => server.transcribe(decode_config, &prompt, resampled),
+        None => transcriber.transcribe(decode_config, &prompt, resampled),
+    }
+}
+
+/// Takes the next job off the queue, transcribes it and emits the outcome. Blocks while the queue is empty,
+/// returns false without doing anything once it is closed.
+pub fn run_next<E: EventSink, T: Transcriber + ?Sized>(events: &E, queue: &InferenceQueue, transcriber: &T) -> bool {
+    let Some((job, caught_up)) = queue.pop() else {
+        return false;
+    };
+    let queue_ms = job.queued_at.elapsed().as_millis() as u64;
+    let started = Instant::now();
+    let resampled = dsp::to_whisper_input(&job.audio, job.sample_rate, job.channels);
+    let result = transcribe_job(transcriber, &job.session, &resampled);
+    let inference_ms = started.elapsed().as_millis() as u64;
+    let session = job.session;
+    match job.kind {
+        JobKind::Final { sequence, start_ms, end_ms } => {
+            let audio_ms = (job.audio.len() / job.channels as usize) as u64 * 1000 / job.sample_rate as u64;
+            events.emit(
+                "whisper:metrics",
+                MetricsPayload {
+                    session: session.id.clone(),
+                    sequence,
+                    audio_ms,
+                    queue_ms,
+                    inference_ms,
+                    rtf: metrics::rtf(audio_ms, inference_ms),
+                },
+            );
+            let mut rejected_text = Vec::new();
+            let result = result.map(|transcript| {
+                let mut transcript = transcript.at(sequence, start_ms, end_ms);
+                transcript.session = session.id.clone();
+                let rejected = filter::apply(&session.config.filter, &mut transcript);
+                rejected_text = rejected
+                    .iter()
+                    .map(|segment| segment.text.clone())
+                    .collect();
+                if !rejected.is_empty() {
+                    events.emit(
+                        "whisper:rejected",
+                        RejectedPayload {
+                            session: session.id.clone(),
+                            sequence,
+                            segments: rejected,
+                        },
+                    );
+                }
+                let commands = session
+                    .config
+                    .commands
+                    .process(&transcript.text, CommandSource::Whisper, Some(&session.id));
+                if let Some(matched) = commands {
+                    for command in matched.commands {
+                        events.emit("voice_command", command);
+                    }
+                    // segments keep the phrase, only the caption text loses it
+                    transcript.text = matched.text;
+                }
+                if !transcript.text.is_empty() {
+                    *session.previous_text.lock().unwrap() = transcript.text.clone();
+                    *session.final_count.lock().unwrap() += 1;
+                    events.emit("whisper:final", transcript.clone());
+                }
+                transcript
+            });
+            if let Err(error) = &result {
+                events.emit(
+                    "whisper:error",
+                    InferenceErrorPayload {
+                        session: session.id.clone(),
+                        sequence,
+                        start_ms,
+                        end_ms,
+                        error: error.clone(),
+                    },
+                );
+            }
+            if let Some(recorder) = &session.recorder {
+                let (text, language, error) = match &result {
+                    Ok(transcript) => (transcript.text.clone(), transcript.language.clone(), None),
+                    Err(e) => (String::new(), None, Some(e.clone())),
+                };
+                let record = UtteranceRecord {
+                    session: session.id.clone(),
+                    sequence,
+                    file: String::new(),
+                    start_ms,
+                    end_ms,
+                    queue_ms,
+                    inference_ms,
+                    text,
+                    rejected: rejected_text,
+                    language,
+                    error,
+                };
+                if let Err(error) = recorder.save_utterance(record, &resampled) {
+                    events.emit(
+                        "whisper:recorder_error",
+                        RecorderErrorPayload {
+                            session: session.id.clone(),
+                            error,
+                        },
+                    );
+                }
+            }
+            for reply in job.replies {
+                let _ = reply.send(result.clone());
+            }
+            queue.finish();
+        }
+        JobKind::Interim { utterance_id } => {
+            if let Ok(mut transcript) = result {
+                filter::apply(&session.config.filter, &mut transcript);
+                // holding the id keeps the final result from resetting the tracker mid-update
+                let current_id = session.utterance_id.lock().unwrap();
+                if !transcript.text.is_empty() && *current_id == utterance_id {
+                    let payload = session
+                        .interim_tracker
+                        .lock()
+                        .unwrap()
+                        .update(&session.id, utterance_id, &transcript.text);
+                    drop(current_id);
+                    events.emit("whisper:interim", payload);
+                }
+            }
+        }
+    }
+    if let Some(backlog) = caught_up {
+        events.emit("whisper:backlog", backlog);
+    }
+    true
+}
+
+/// Runs every queued job on one thread so results leave in the order utterances ended. Returns once the queue is closed.
+pub fn inference_worker<E: EventSink, T: Transcriber + ?Sized>(events: E, queue: &InferenceQueue, transcriber: &T) {
+    while run_next(&events, queue, transcriber) {}
+}
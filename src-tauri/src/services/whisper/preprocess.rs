@@ -0,0 +1,485 @@
+use serde::Deserialize;
+use std::f64::consts::PI;
+
+use super::dsp::fft;
+
+/// The gate pulls closed audio down to this instead of hard muting, which pumps less.
+const GATE_FLOOR: f32 = 0.05;
+/// Below this input power the AGC eases back to unity gain, so steady room noise is not lifted past the VAD threshold.
+/// Quieter voice could not reach the default -20 dB target with 20 dB of gain anyway.
+const AGC_FLOOR_DB: f32 = -45.0;
+/// Lowest gain spectral subtraction applies to a bin, deeper cuts leave musical noise.
+const SUPPRESSION_FLOOR: f32 = 0.1;
+/// STFT frames averaged into the first noise estimate.
+const NOISE_INIT_FRAMES: u64 = 10;
+/// A bin this many times louder than its noise estimate is taken for speech.
+const NOISE_UPDATE_RATIO: f32 = 4.0;
+/// Per-frame smoothing of a bin's noise estimate while it looks like noise.
+const NOISE_SMOOTHING: f32 = 0.05;
+/// Per-frame smoothing while it looks like speech, slow so a louder fan is still picked up.
+const NOISE_RISE: f32 = 0.002;
+
+/// Stages run before the VAD and recognition, all off by default.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PreprocessOptions {
+    /// Removes rumble, handling noise and DC offset.
+    pub high_pass: bool,
+    pub high_pass_hz: f32,
+    /// Spectral subtraction of a running noise estimate, fans and hum.
+    pub noise_suppression: bool,
+    /// How much of the noise estimate is subtracted, above 1 removes more noise and more voice.
+    pub suppression_strength: f32,
+    pub noise_gate: bool,
+    pub gate_threshold_db: f32,
+    /// Automatic gain control towards `agc_target_db` RMS.
+    pub agc: bool,
+    pub agc_target_db: f32,
+    pub agc_max_gain_db: f32,
+}
+
+impl Default for PreprocessOptions {
+    fn default() -> Self {
+        Self {
+            high_pass: false,
+            high_pass_hz: 80.0,
+            noise_suppression: false,
+            suppression_strength: 1.5,
+            noise_gate: false,
+            gate_threshold_db: -50.0,
+            agc: false,
+            agc_target_db: -20.0,
+            agc_max_gain_db: 20.0,
+        }
+    }
+}
+
+fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// One-pole smoothing coefficient reaching ~63% of a step after `ms`.
+fn smoothing(ms: f32, sample_rate: u32) -> f32 {
+    (-1.0 / (ms / 1000.0 * sample_rate as f32).max(1.0)).exp()
+}
+
+/// Second order Butterworth high-pass, RBJ cookbook coefficients.
+struct HighPass {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl HighPass {
+    fn new(cutoff_hz: f32, sample_rate: u32) -> Self {
+        let cutoff = (cutoff_hz as f64).clamp(10.0, sample_rate as f64 * 0.45);
+        let w0 = 2.0 * PI * cutoff / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * std::f64::consts::FRAC_1_SQRT_2);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b: [(1.0 + cos) / 2.0 / a0, -(1.0 + cos) / a0, (1.0 + cos) / 2.0 / a0],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            z: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            let x = *sample as f64;
+            let y = self.b[0] * x + self.z[0];
+            self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+            self.z[1] = self.b[2] * x - self.a[1] * y;
+            *sample = y as f32;
+        }
+    }
+}
+
+struct NoiseGate {
+    threshold: f32,
+    envelope: f32,
+    envelope_release: f32,
+    gain: f32,
+    open_coef: f32,
+    close_coef: f32,
+}
+
+impl NoiseGate {
+    fn new(threshold_db: f32, sample_rate: u32) -> Self {
+        Self {
+            threshold: db_to_amplitude(threshold_db),
+            envelope: 0.0,
+            // the slow envelope release doubles as hold time between words
+            envelope_release: smoothing(100.0, sample_rate),
+            gain: GATE_FLOOR,
+            open_coef: smoothing(1.0, sample_rate),
+            close_coef: smoothing(50.0, sample_rate),
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            self.envelope = sample.abs().max(self.envelope * self.envelope_release);
+            let (target, coef) = if self.envelope > self.threshold {
+                (1.0, self.open_coef)
+            } else {
+                (GATE_FLOOR, self.close_coef)
+            };
+            self.gain = target + (self.gain - target) * coef;
+            *sample *= self.gain;
+        }
+    }
+}
+
+struct Agc {
+    target: f32,
+    max_gain: f32,
+    floor_power: f32,
+    power: f32,
+    power_coef: f32,
+    gain: f32,
+    attack_coef: f32,
+    release_coef: f32,
+}
+
+impl Agc {
+    fn new(target_db: f32, max_gain_db: f32, sample_rate: u32) -> Self {
+        Self {
+            target: db_to_amplitude(target_db),
+            max_gain: db_to_amplitude(max_gain_db.max(0.0)),
+            floor_power: db_to_amplitude(AGC_FLOOR_DB).powi(2),
+            power: 0.0,
+            power_coef: smoothing(300.0, sample_rate),
+            gain: 1.0,
+            // turning down is fast so a loud onset does not clip for long
+            attack_coef: smoothing(50.0, sample_rate),
+            release_coef: smoothing(1000.0, sample_rate),
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            self.power = *sample * *sample + (self.power - *sample * *sample) * self.power_coef;
+            let desired = if self.power > self.floor_power {
+                (self.target / self.power.sqrt()).clamp(1.0 / self.max_gain, self.max_gain)
+            } else {
+                1.0
+            };
+            let coef = if desired < self.gain { self.attack_coef } else { self.release_coef };
+            self.gain = desired + (self.gain - desired) * coef;
+            *sample = (*sample * self.gain).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// Overlap-add STFT with a sqrt-Hann window at 50% overlap, which reconstructs the input
+/// exactly when no bin is touched. Output lags the input by one hop.
+struct SpectralSubtractor {
+    fft_len: usize,
+    hop: usize,
+    window: Vec<f32>,
+    input: Vec<f32>,
+    output: Vec<f32>,
+    /// Noise power per bin.
+    noise: Vec<f32>,
+    frames: u64,
+    strength: f32,
+}
+
+impl SpectralSubtractor {
+    fn new(strength: f32, sample_rate: u32) -> Self {
+        // ~20 ms frames, rounded up to what the FFT takes
+        let fft_len = (sample_rate as usize / 50).next_power_of_two().max(64);
+        let hop = fft_len / 2;
+        let window = (0..fft_len)
+            .map(|i| (0.5 - 0.5 * (2.0 * PI * i as f64 / fft_len as f64).cos()).sqrt() as f32)
+            .collect();
+        Self {
+            fft_len,
+            hop,
+            window,
+            input: vec![0.0; fft_len - hop],
+            output: vec![0.0; fft_len],
+            noise: vec![0.0; fft_len / 2 + 1],
+            frames: 0,
+            strength,
+        }
+    }
+
+    fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.input.extend_from_slice(samples);
+        let mut out = Vec::with_capacity(samples.len() + self.hop);
+        let mut re = vec![0.0; self.fft_len];
+        let mut im = vec![0.0; self.fft_len];
+        while self.input.len() >= self.fft_len {
+            for ((re, x), w) in re.iter_mut().zip(&self.input).zip(&self.window) {
+                *re = x * w;
+            }
+            im.fill(0.0);
+            fft(&mut re, &mut im, false);
+            self.suppress(&mut re, &mut im);
+            fft(&mut re, &mut im, true);
+            let scale = 1.0 / self.fft_len as f32;
+            for ((out, re), w) in self.output.iter_mut().zip(&re).zip(&self.window) {
+                *out += re * scale * w;
+            }
+            out.extend(self.output.drain(..self.hop));
+            self.output.resize(self.fft_len, 0.0);
+            self.input.drain(..self.hop);
+        }
+        out
+    }
+
+    fn suppress(&mut self, re: &mut [f32], im: &mut [f32]) {
+        self.frames += 1;
+        let bins = self.fft_len / 2 + 1;
+        for k in 0..bins {
+            let power = re[k] * re[k] + im[k] * im[k];
+            let noise = &mut self.noise[k];
+            if self.frames <= NOISE_INIT_FRAMES {
+                *noise += (power - *noise) / self.frames as f32;
+                continue;
+            }
+            let rate = if power < *noise * NOISE_UPDATE_RATIO {
+                NOISE_SMOOTHING
+            } else {
+                NOISE_RISE
+            };
+            *noise += (power - *noise) * rate;
+
+            let gain = if power > 0.0 {
+                (1.0 - self.strength * *noise / power)
+                    .max(SUPPRESSION_FLOOR * SUPPRESSION_FLOOR)
+                    .sqrt()
+            } else {
+                SUPPRESSION_FLOOR
+            };
+            re[k] *= gain;
+            im[k] *= gain;
+            // keep the spectrum conjugate symmetric so the output stays real
+            if k > 0 && k < self.fft_len / 2 {
+                re[self.fft_len - k] *= gain;
+                im[self.fft_len - k] *= gain;
+            }
+        }
+    }
+}
+
+/// The enabled stages for one channel.
+struct Chain {
+    high_pass: Option<HighPass>,
+    suppressor: Option<SpectralSubtractor>,
+    gate: Option<NoiseGate>,
+    agc: Option<Agc>,
+}
+
+impl Chain {
+    fn process(&mut self, mut samples: Vec<f32>) -> Vec<f32> {
+        if let Some(high_pass) = &mut self.high_pass {
+            high_pass.process(&mut samples);
+        }
+        // suppression first so the gate and AGC see the cleaned level
+        if let Some(suppressor) = &mut self.suppressor {
+            samples = suppressor.process(&samples);
+        }
+        if let Some(gate) = &mut self.gate {
+            gate.process(&mut samples);
+        }
+        if let Some(agc) = &mut self.agc {
+            agc.process(&mut samples);
+        }
+        samples
+    }
+}
+
+/// Runs the enabled stages on every channel of interleaved audio, keeping the layout.
+pub struct Preprocessor {
+    chains: Vec<Chain>,
+}
+
+impl Preprocessor {
+    /// None when every stage is off.
+    pub fn new(options: &PreprocessOptions, sample_rate: u32, channels: u16) -> Option<Self> {
+        if !(options.high_pass || options.noise_suppression || options.noise_gate || options.agc) {
+            return None;
+        }
+        let chains = (0..channels.max(1))
+            .map(|_| Chain {
+                high_pass: options
+                    .high_pass
+                    .then(|| HighPass::new(options.high_pass_hz, sample_rate)),
+                suppressor: options
+                    .noise_suppression
+                    .then(|| SpectralSubtractor::new(options.suppression_strength, sample_rate)),
+                gate: options
+                    .noise_gate
+                    .then(|| NoiseGate::new(options.gate_threshold_db, sample_rate)),
+                agc: options
+                    .agc
+                    .then(|| Agc::new(options.agc_target_db, options.agc_max_gain_db, sample_rate)),
+            })
+            .collect();
+        Some(Self { chains })
+    }
+
+    /// Noise suppression holds back up to one STFT hop, so the output can be shorter than the input.
+    pub fn process(&mut self, data: &[f32]) -> Vec<f32> {
+        let channels = self.chains.len();
+        if channels == 1 {
+            return self.chains[0].process(data.to_vec());
+        }
+        let processed: Vec<Vec<f32>> = self
+            .chains
+            .iter_mut()
+            .enumerate()
+            .map(|(c, chain)| chain.process(data.iter().skip(c).step_by(channels).copied().collect()))
+            .collect();
+        let frames = processed.iter().map(Vec::len).min().unwrap_or(0);
+        (0..frames)
+            .flat_map(|i| processed.iter().map(move |channel| channel[i]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dsp::calculate_rms_db;
+    use super::super::vad::{Vad, VadConfig, VadEvent};
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn sine(freq: f64, secs: f64, amplitude: f32) -> Vec<f32> {
+        (0..(RATE as f64 * secs) as usize)
+            .map(|i| amplitude * (2.0 * PI * freq * i as f64 / RATE as f64).sin() as f32)
+            .collect()
+    }
+
+    fn noise(secs: f64, amplitude: f32) -> Vec<f32> {
+        let mut seed: u32 = 0x9e37_79b9;
+        (0..(RATE as f64 * secs) as usize)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    fn options() -> PreprocessOptions {
+        PreprocessOptions::default()
+    }
+
+    fn tail_db(samples: &[f32]) -> f32 {
+        calculate_rms_db(&samples[samples.len() / 2..])
+    }
+
+    #[test]
+    fn nothing_enabled_builds_no_preprocessor() {
+        assert!(Preprocessor::new(&options(), RATE, 1).is_none());
+    }
+
+    #[test]
+    fn high_pass_removes_rumble_and_keeps_voice_band() {
+        let opts = PreprocessOptions {
+            high_pass: true,
+            ..options()
+        };
+        let mut rumble = Preprocessor::new(&opts, RATE, 1).unwrap();
+        let mut voice = Preprocessor::new(&opts, RATE, 1).unwrap();
+        assert!(tail_db(&rumble.process(&sine(20.0, 1.0, 0.5))) < tail_db(&sine(20.0, 1.0, 0.5)) - 20.0);
+        assert!((tail_db(&voice.process(&sine(1000.0, 1.0, 0.5))) - tail_db(&sine(1000.0, 1.0, 0.5))).abs() < 0.5);
+    }
+
+    #[test]
+    fn gate_attenuates_quiet_input_only() {
+        let opts = PreprocessOptions {
+            noise_gate: true,
+            ..options()
+        };
+        let quiet = noise(1.0, 0.001);
+        let loud = sine(300.0, 1.0, 0.3);
+        assert!(tail_db(&Preprocessor::new(&opts, RATE, 1).unwrap().process(&quiet)) < tail_db(&quiet) - 20.0);
+        assert!((tail_db(&Preprocessor::new(&opts, RATE, 1).unwrap().process(&loud)) - tail_db(&loud)).abs() < 0.5);
+    }
+
+    #[test]
+    fn agc_does_not_lift_room_noise_into_speech() {
+        // a steady fan or mains hum at -55 dB
+        let hum = sine(100.0, 10.0, db_to_amplitude(-55.0) * std::f32::consts::SQRT_2);
+        let opts = PreprocessOptions { agc: true, ..options() };
+        let output = Preprocessor::new(&opts, RATE, 1).unwrap().process(&hum);
+        let mut vad = Vad::new(VadConfig::default(), RATE, 1);
+        let events: Vec<VadEvent> = output
+            .chunks(480)
+            .flat_map(|chunk| vad.push(chunk))
+            .collect();
+        assert!(!events.contains(&VadEvent::Start), "noise left the AGC at {} dB", tail_db(&output));
+    }
+
+    #[test]
+    fn agc_brings_quiet_voice_to_target() {
+        let opts = PreprocessOptions { agc: true, ..options() };
+        let output = Preprocessor::new(&opts, RATE, 1)
+            .unwrap()
+            .process(&sine(300.0, 4.0, 0.02));
+        assert!((tail_db(&output) - opts.agc_target_db).abs() < 1.5, "{}", tail_db(&output));
+    }
+
+    #[test]
+    fn suppressor_without_subtraction_reconstructs_input() {
+        let opts = PreprocessOptions {
+            noise_suppression: true,
+            suppression_strength: 0.0,
+            ..options()
+        };
+        let mut preprocessor = Preprocessor::new(&opts, RATE, 1).unwrap();
+        let input = sine(440.0, 0.5, 0.5);
+        let output: Vec<f32> = input
+            .chunks(300)
+            .flat_map(|chunk| preprocessor.process(chunk))
+            .collect();
+        let hop = 256;
+        assert!(input.len() - output.len() <= hop);
+        for (out, x) in output[hop..].iter().zip(&input) {
+            assert!((out - x).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn suppressor_lowers_steady_noise_and_keeps_tone() {
+        let opts = PreprocessOptions {
+            noise_suppression: true,
+            ..options()
+        };
+        let mut preprocessor = Preprocessor::new(&opts, RATE, 1).unwrap();
+        let hiss = noise(2.0, 0.02);
+        let cleaned = preprocessor.process(&hiss);
+        // white noise bins swing around their mean, plain power subtraction takes about 6 dB off
+        assert!(tail_db(&cleaned) < tail_db(&hiss) - 5.0, "{} {}", tail_db(&cleaned), tail_db(&hiss));
+
+        let mut tone = sine(440.0, 1.0, 0.3);
+        for (t, n) in tone.iter_mut().zip(noise(1.0, 0.02)) {
+            *t += n;
+        }
+        let with_tone = preprocessor.process(&tone);
+        assert!(
+            (tail_db(&with_tone) - tail_db(&sine(440.0, 1.0, 0.3))).abs() < 1.0,
+            "{}",
+            tail_db(&with_tone)
+        );
+    }
+
+    #[test]
+    fn keeps_interleaved_layout() {
+        let opts = PreprocessOptions {
+            high_pass: true,
+            ..options()
+        };
+        let left = sine(1000.0, 0.5, 0.5);
+        let stereo: Vec<f32> = left.iter().flat_map(|s| [*s, 0.0]).collect();
+        let output = Preprocessor::new(&opts, RATE, 2).unwrap().process(&stereo);
+        assert_eq!(output.len(), stereo.len());
+        assert!(output.iter().skip(1).step_by(2).all(|s| *s == 0.0));
+        assert!(output.iter().step_by(2).any(|s| s.abs() > 0.1));
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    time::Instant,
+};
+
+use tokio::sync::oneshot;
+
+use super::{session::Session, transcript::Transcript};
+
+pub const DEFAULT_CAPACITY: usize = 4;
+
+/// What to do with a new utterance when the worker is `capacity` utterances behind.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BacklogPolicy {
+    /// Discard the oldest queued utterance.
+    DropOldest,
+    /// Append the audio to the newest queued utterance, one bigger job instead of two.
+    Merge,
+    /// Wait for the worker, this stalls whoever is feeding audio with the session locks held.
+    /// Only for sources that can wait, `start_recording` refuses it for a local capture device.
+    Block,
+}
+
+pub enum JobKind {
+    Final { sequence: u64, start_ms: u64, end_ms: u64 },
+    Interim { utterance_id: u64 },
+}
+
+pub struct Job {
+    pub kind: JobKind,
+    pub session: Arc<Session>,
+    /// Get the result of a final job when someone is waiting on it, `end_utterance` for one.
+    /// Merged jobs carry the replies of every utterance in them.
+    pub replies: Vec<oneshot::Sender<Result<Transcript, String>>>,
+    pub audio: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// When the job was queued, merged jobs keep the older time.
+    pub queued_at: Instant,
+}
+
+/// Payload of `whisper:backlog`, for the session whose utterances are piling up.
+#[derive(Clone, Serialize)]
+pub struct BacklogPayload {
+    pub session: String,
+    pub pending: usize,
+    pub capacity: usize,
+    pub policy: BacklogPolicy,
+    pub dropped: u64,
+    pub merged: u64,
+}
+
+/// Backlog counters of one session, kept by the queue.
+#[derive(Default)]
+pub struct BacklogState {
+    pub dropped: u64,
+    pub merged: u64,
+    /// A backlog was reported and the all-clear has not been sent yet.
+    pub behind: bool,
+}
+
+struct Inner {
+    finals: VecDeque<Job>,
+    /// Only the newest interim window of each session is worth transcribing, older ones are replaced.
+    interims: VecDeque<Job>,
+    /// Session of the final the worker is transcribing right now.
+    running: Option<Arc<Session>>,
+    /// Set by `close`, the worker stops once nothing is left.
+    closed: bool,
+}
+
+impl Inner {
+    fn pending(&self, session: &Arc<Session>) -> usize {
+        self.finals
+            .iter()
+            .filter(|job| Arc::ptr_eq(&job.session, session))
+            .count()
+    }
+
+    fn backlog(&self, session: &Arc<Session>) -> BacklogPayload {
+        let state = session.backlog.lock().unwrap();
+        BacklogPayload {
+            session: session.id.clone(),
+            pending: self.pending(session),
+            capacity: session.config.queue_capacity,
+            policy: session.config.backlog_policy,
+            dropped: state.dropped,
+            merged: state.merged,
+        }
+    }
+}
+
+/// FIFO feeding the single inference worker, so results come out in utterance order.
+/// Each session is bounded by its own capacity and policy, one busy session does not drop another's utterances.
+pub struct InferenceQueue {
+    inner: Mutex<Inner>,
+    changed: Condvar,
+}
+
+impl Default for InferenceQueue {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                finals: VecDeque::new(),
+                interims: VecDeque::new(),
+                running: None,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+}
+
+impl InferenceQueue {
+    /// Queues a finished utterance. Returns the backlog state when the worker is falling behind on its session.
+    pub fn push_final(&self, job: Job) -> Option<BacklogPayload> {
+        let session = job.session.clone();
+        let capacity = session.config.queue_capacity.max(1);
+        let mut inner = self.inner.lock().unwrap();
+        // a final supersedes whatever interim window of its session was waiting
+        inner
+            .interims
+            .retain(|interim| !Arc::ptr_eq(&interim.session, &session));
+
+        let mut dropped = false;
+        if inner.pending(&session) >= capacity {
+            match session.config.backlog_policy {
+                BacklogPolicy::DropOldest => {
+                    dropped = drop_oldest(&mut inner.finals, &session);
+                }
+                BacklogPolicy::Merge => {
+                    if let Some(last) = inner
+                        .finals
+                        .iter_mut()
+                        .rev()
+                        .find(|queued| Arc::ptr_eq(&queued.session, &session))
+                    {
+                        merge(last, job);
+                        {
+                            let mut state = session.backlog.lock().unwrap();
+                            state.merged += 1;
+                            state.behind = true;
+                        }
+                        let backlog = inner.backlog(&session);
+                        self.changed.notify_all();
+                        return Some(backlog);
+                    }
+                }
+                BacklogPolicy::Block => {
+                    while inner.pending(&session) >= capacity {
+                        inner = self.changed.wait(inner).unwrap();
+                    }
+                }
+            }
+        }
+        inner.finals.push_back(job);
+        self.changed.notify_all();
+        if dropped || inner.pending(&session) > 1 {
+            session.backlog.lock().unwrap().behind = true;
+            Some(inner.backlog(&session))
+        } else {
+            None
+        }
+    }
+
+    pub fn push_interim(&self, job: Job) {
+        let mut inner = self.inner.lock().unwrap();
+        match inner
+            .interims
+            .iter_mut()
+            .find(|interim| Arc::ptr_eq(&interim.session, &job.session))
+        {
+            Some(interim) => *interim = job,
+            None => inner.interims.push_back(job),
+        }
+        self.changed.notify_all();
+    }
+
+    /// Blocks until there is work, finals always go before interim windows.
+    /// Also returns the backlog state once a reported backlog has been worked off.
+    /// `None` once the queue is closed and empty.
+    pub fn pop(&self) -> Option<(Job, Option<BacklogPayload>)> {
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            if let Some(job) = inner.finals.pop_front() {
+                inner.running = Some(job.session.clone());
+                self.changed.notify_all();
+                let caught_up = {
+                    let mut state = job.session.backlog.lock().unwrap();
+                    let caught_up = state.behind && inner.pending(&job.session) == 0;
+                    if caught_up {
+                        state.behind = false;
+                    }
+                    caught_up
+                };
+                let backlog = caught_up.then(|| inner.backlog(&job.session));
+                return Some((job, backlog));
+            }
+            if let Some(job) = inner.interims.pop_front() {
+                return Some((job, None));
+            }
+            if inner.closed {
+                return None;
+            }
+            inner = self.changed.wait(inner).unwrap();
+        }
+    }
+
+    /// Lets the worker exit after the queued jobs, nothing should be pushed afterwards.
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    /// Called by the worker once the popped job's result is out.
+    pub fn finish(&self) {
+        self.inner.lock().unwrap().running = None;
+        self.changed.notify_all();
+    }
+
+    /// Blocks until every final of `session` has been transcribed, its pending interim window is dropped.
+    pub fn drain(&self, session: &Arc<Session>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .interims
+            .retain(|interim| !Arc::ptr_eq(&interim.session, session));
+        while inner
+            .finals
+            .iter()
+            .any(|job| Arc::ptr_eq(&job.session, session))
+            || inner
+                .running
+                .as_ref()
+                .map_or(false, |running| Arc::ptr_eq(running, session))
+        {
+            inner = self.changed.wait(inner).unwrap();
+        }
+    }
+}
+
+fn drop_oldest(finals: &mut VecDeque<Job>, session: &Arc<Session>) -> bool {
+    let Some(index) = finals
+        .iter()
+        .position(|job| Arc::ptr_eq(&job.session, session))
+    else {
+        return false;
+    };
+    finals.remove(index);
+    session.backlog.lock().unwrap().dropped += 1;
+    true
+}
+
+fn merge(last: &mut Job, new: Job) {
+    if let (JobKind::Final { end_ms, .. }, JobKind::Final { end_ms: new_end, .. }) = (&mut last.kind, &new.kind) {
+        *end_ms = *new_end;
+    }
+    last.audio.extend_from_slice(&new.audio);
+    last.replies.extend(new.replies);
+}
@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::dsp::{self, WHISPER_SAMPLE_RATE};
+
+const SIDECAR_FILE: &str = "utterances.jsonl";
+
+/// `start_recording` param, keeps what Whisper heard for debugging and correction lists.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RecorderOptions {
+    /// Write every utterance Whisper receives, with a line in `utterances.jsonl`.
+    pub save_utterances: bool,
+    /// Minutes of audio kept for `save_replay`, 0 turns the buffer off.
+    pub replay_minutes: u64,
+    /// Where the session folder goes, `whisper/recordings` in the app data dir by default.
+    /// `start_recording` replaces it with the session folder itself.
+    pub dir: Option<PathBuf>,
+}
+
+impl RecorderOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.save_utterances || self.replay_minutes > 0
+    }
+}
+
+/// One line of `utterances.jsonl`.
+#[derive(Clone, Debug, Serialize)]
+pub struct UtteranceRecord {
+    pub session: String,
+    pub sequence: u64,
+    /// WAV next to the sidecar, 16 kHz mono as Whisper got it.
+    pub file: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub queue_ms: u64,
+    pub inference_ms: u64,
+    /// Text after filtering, empty when everything was rejected.
+    pub text: String,
+    /// Segments the filter dropped.
+    pub rejected: Vec<String>,
+    pub language: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Payload of `whisper:recorder_error`, recording goes on without the recorder output.
+#[derive(Clone, Serialize)]
+pub struct RecorderErrorPayload {
+    pub session: String,
+    pub error: String,
+}
+
+/// The last few minutes of mono audio, oldest samples fall out first.
+pub struct ReplayBuffer {
+    samples: VecDeque<i16>,
+    capacity: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(minutes: u64, sample_rate: u32) -> Self {
+        let capacity = (minutes * 60 * sample_rate as u64) as usize;
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, mono: &[f32]) {
+        let skip = mono.len().saturating_sub(self.capacity);
+        for sample in &mono[skip..] {
+            if self.samples.len() == self.capacity {
+                self.samples.pop_front();
+            }
+            self.samples
+                .push_back((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<f32> {
+        self.samples
+            .iter()
+            .map(|s| *s as f32 / i16::MAX as f32)
+            .collect()
+    }
+}
+
+fn write_wav(path: &Path, audio: &[f32]) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: WHISPER_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    for sample in audio {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(sample).map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+/// Per-session recorder, writes into its own folder.
+pub struct Recorder {
+    dir: PathBuf,
+    save_utterances: bool,
+    /// Rate of the session, the buffer is resampled when saved.
+    sample_rate: u32,
+    replay: Option<Mutex<ReplayBuffer>>,
+}
+
+impl Recorder {
+    pub fn new(options: &RecorderOptions, sample_rate: u32) -> Self {
+        Self {
+            dir: options.dir.clone().unwrap_or_default(),
+            save_utterances: options.save_utterances,
+            sample_rate,
+            replay: (options.replay_minutes > 0).then(|| Mutex::new(ReplayBuffer::new(options.replay_minutes, sample_rate))),
+        }
+    }
+
+    /// Feeds the replay buffer with interleaved audio in the session's format.
+    pub fn push_replay(&self, data: &[f32], channels: u16) {
+        if let Some(replay) = &self.replay {
+            replay.lock().unwrap().push(&dsp::downmix(data, channels));
+        }
+    }
+
+    /// Writes the utterance WAV and its sidecar line. `audio` is 16 kHz mono.
+    pub fn save_utterance(&self, mut record: UtteranceRecord, audio: &[f32]) -> Result<(), String> {
+        if !self.save_utterances {
+            return Ok(());
+        }
+        record.file = format!("utterance-{:05}.wav", record.sequence);
+        write_wav(&self.dir.join(&record.file), audio)?;
+        let mut line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(SIDECAR_FILE))
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("Failed to write {}: {}", SIDECAR_FILE, e))
+    }
+
+    /// Dumps the replay buffer as 16 kHz mono, into the session folder unless `path` is given.
+    pub fn save_replay(&self, path: Option<PathBuf>) -> Result<PathBuf, String> {
+        let replay = self
+            .replay
+            .as_ref()
+            .ok_or("The replay buffer is off for this session")?;
+        let audio = replay.lock().unwrap().snapshot();
+        let path = path.unwrap_or_else(|| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            self.dir.join(format!("replay-{}.wav", now.as_secs()))
+        });
+        write_wav(&path, &dsp::resample(&audio, self.sample_rate, WHISPER_SAMPLE_RATE))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whisper-recorder-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replay_keeps_only_the_newest_audio() {
+        let mut replay = ReplayBuffer::new(1, 10);
+        replay.push(&[0.0; 500]);
+        replay.push(&[0.5; 550]);
+        let audio = replay.snapshot();
+        assert_eq!(audio.len(), 600);
+        assert_eq!(audio.iter().filter(|s| **s > 0.4).count(), 550);
+        assert!(audio[..50].iter().all(|s| *s == 0.0));
+
+        replay.push(&[-0.5; 700]);
+        assert!(replay.snapshot().iter().all(|s| *s < -0.4));
+    }
+
+    #[test]
+    fn saves_replay_resampled_to_16k() {
+        let dir = scratch_dir("replay");
+        let options = RecorderOptions {
+            replay_minutes: 1,
+            dir: Some(dir.clone()),
+            ..RecorderOptions::default()
+        };
+        let recorder = Recorder::new(&options, 48000);
+        recorder.push_replay(&vec![0.1; 48000 * 2 * 3], 2);
+
+        let path = recorder.save_replay(None).unwrap();
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, WHISPER_SAMPLE_RATE);
+        assert_eq!(reader.spec().channels, 1);
+        assert!((reader.duration() as i64 - 48000).abs() <= 1);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn writes_wav_and_sidecar_line_per_utterance() {
+        let dir = scratch_dir("utterances");
+        let options = RecorderOptions {
+            save_utterances: true,
+            dir: Some(dir.clone()),
+            ..RecorderOptions::default()
+        };
+        let recorder = Recorder::new(&options, 16000);
+        assert!(recorder.save_replay(None).is_err());
+        for sequence in 0..2 {
+            let record = UtteranceRecord {
+                session: "default".to_string(),
+                sequence,
+                file: String::new(),
+                start_ms: sequence * 2000,
+                end_ms: sequence * 2000 + 1000,
+                queue_ms: 3,
+                inference_ms: 250,
+                text: format!("line {}", sequence),
+                rejected: Vec::new(),
+                language: Some("en".to_string()),
+                error: None,
+            };
+            recorder.save_utterance(record, &[0.2; 16000]).unwrap();
+        }
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(dir.join(SIDECAR_FILE))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["text"], "line 1");
+        assert_eq!(lines[1]["file"], "utterance-00001.wav");
+        let reader = hound::WavReader::open(dir.join("utterance-00001.wav")).unwrap();
+        assert_eq!(reader.duration(), 16000);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
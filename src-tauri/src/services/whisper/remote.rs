@@ -0,0 +1,55 @@
+use tauri::{AppHandle, Runtime};
+
+use super::{dsp, pipeline, session::DEFAULT_SESSION, WhisperState};
+
+/// Audio arriving from a remote microphone in its own format. Converts it into whatever
+/// the session it feeds expects before it reaches the VAD.
+pub struct RemoteSource {
+    session: String,
+    sample_rate: u32,
+    channels: u16,
+    /// Rate the resampler was built for, rebuilt when the session is restarted with another one.
+    target_rate: u32,
+    resampler: dsp::StreamResampler,
+}
+
+impl RemoteSource {
+    pub fn new(session: Option<String>, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            session: session.unwrap_or_else(|| DEFAULT_SESSION.to_string()),
+            sample_rate,
+            channels,
+            target_rate: sample_rate,
+            resampler: dsp::StreamResampler::new(sample_rate, sample_rate),
+        }
+    }
+
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// Takes interleaved samples in the source format. Dropped while the session is not recording.
+    pub fn push<R: Runtime>(&mut self, state: &WhisperState, app: &AppHandle<R>, samples: &[f32]) {
+        let Some(session) = state.session(&self.session) else {
+            return;
+        };
+        let target_rate = session.config.sample_rate;
+        let target_channels = session.config.channels;
+        if target_rate != self.target_rate {
+            self.target_rate = target_rate;
+            self.resampler = dsp::StreamResampler::new(self.sample_rate, target_rate);
+        }
+
+        let mono = self
+            .resampler
+            .process(&dsp::downmix(samples, self.channels));
+        let data = if target_channels > 1 {
+            mono.iter()
+                .flat_map(|s| std::iter::repeat(*s).take(target_channels as usize))
+                .collect()
+        } else {
+            mono
+        };
+        pipeline::process_audio_chunk(&state.queue_for(&session), &session, app, data);
+    }
+}
@@ -0,0 +1,109 @@
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How often the idle timeout is checked.
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// whisper.cpp keeps KV caches and compute buffers next to the weights, these grow with the model.
+const OVERHEAD_RATIO: f64 = 0.3;
+const OVERHEAD_MB: u64 = 150;
+
+/// The model picked last. It stays after an unload so the next start can load it again.
+pub struct ModelSlot {
+    pub id: String,
+    pub path: PathBuf,
+    file_size: u64,
+    last_used: Instant,
+}
+
+impl ModelSlot {
+    pub fn new(id: &str, path: &Path) -> Self {
+        Self {
+            id: id.to_string(),
+            path: path.to_path_buf(),
+            file_size: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            last_used: Instant::now(),
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.last_used = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_used.elapsed()
+    }
+
+    /// Rough resident size once loaded, close to the figures whisper.cpp lists for its models.
+    pub fn approx_memory_mb(&self) -> u64 {
+        let file_mb = self.file_size as f64 / (1024.0 * 1024.0);
+        (file_mb * (1.0 + OVERHEAD_RATIO)) as u64 + OVERHEAD_MB
+    }
+
+    /// Unix time in ms, for the UI.
+    pub fn last_used_ms(&self) -> u64 {
+        let last_used = SystemTime::now() - self.last_used.elapsed();
+        last_used
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+}
+
+/// Result of `model_status`.
+#[derive(Clone, Serialize)]
+pub struct ModelStatus {
+    /// Model that is loaded, or gets loaded again on the next start.
+    pub model: Option<String>,
+    pub loaded: bool,
+    /// Only set while loaded.
+    pub memory_mb: Option<u64>,
+    pub last_used_ms: Option<u64>,
+    pub idle_timeout_minutes: Option<u64>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnloadReason {
+    Manual,
+    Idle,
+}
+
+/// Payload of `whisper:model_unloaded`.
+#[derive(Clone, Serialize)]
+pub struct UnloadedPayload {
+    pub model: String,
+    pub reason: UnloadReason,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_memory_from_file_size() {
+        let mut slot = ModelSlot::new("medium", Path::new("/nonexistent/ggml-medium.bin"));
+        assert_eq!(slot.approx_memory_mb(), OVERHEAD_MB);
+        slot.file_size = 1533 * 1024 * 1024;
+        // whisper.cpp reports about 2.1 GB for medium
+        let mb = slot.approx_memory_mb();
+        assert!(mb > 1900 && mb < 2300, "{} MB", mb);
+    }
+
+    #[test]
+    fn reports_last_use_as_unix_time() {
+        let mut slot = ModelSlot::new("tiny", Path::new("/nonexistent/ggml-tiny.bin"));
+        slot.last_used = Instant::now() - Duration::from_secs(90);
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        assert!(now_ms - slot.last_used_ms() >= 90_000);
+        assert!(slot.idle_for() >= Duration::from_secs(90));
+        slot.touch();
+        assert!(slot.idle_for() < Duration::from_secs(1));
+    }
+}
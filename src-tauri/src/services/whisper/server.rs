@@ -0,0 +1,273 @@
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use std::{io::Cursor, time::Duration};
+
+use super::{
+    decode::{DecodeConfig, AUTO_LANGUAGE},
+    dsp::WHISPER_SAMPLE_RATE,
+    transcriber::Transcriber,
+    transcript::{Segment, Transcript},
+};
+
+const DEFAULT_MODEL: &str = "whisper-1";
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// An endpoint that cannot be reached fails fast instead of using up the whole timeout.
+const CONNECT_TIMEOUT_SECS: u64 = 5;
+
+/// Where a session sends its utterances instead of the loaded model.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerOptions {
+    /// Full URL, `http://localhost:8000/v1/audio/transcriptions` for faster-whisper-server.
+    pub endpoint: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Sent as `model`, servers hosting a single model ignore it.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl ServerOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        let url = reqwest::Url::parse(&self.endpoint).map_err(|e| format!("Invalid server endpoint: {}", e))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(format!("Invalid server endpoint: {}", self.endpoint));
+        }
+        Ok(())
+    }
+
+    /// OpenAI serves translation on its own route, whisper.cpp and faster-whisper mirror that.
+    fn url(&self, translate: bool) -> String {
+        match self.endpoint.strip_suffix("/transcriptions") {
+            Some(base) if translate => format!("{}/translations", base),
+            _ => self.endpoint.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ServerSegment {
+    start: f64,
+    end: f64,
+    text: String,
+    #[serde(default)]
+    avg_logprob: Option<f32>,
+    #[serde(default)]
+    no_speech_prob: Option<f32>,
+}
+
+/// `verbose_json` response, plain `json` servers only send the text.
+#[derive(Deserialize)]
+struct ServerResponse {
+    text: String,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    segments: Option<Vec<ServerSegment>>,
+}
+
+fn secs_to_ms(secs: f64) -> u64 {
+    (secs.max(0.0) * 1000.0).round() as u64
+}
+
+impl ServerResponse {
+    fn into_transcript(self, requested_language: &str, duration_ms: u64) -> Transcript {
+        let segments = match self.segments {
+            Some(segments) => segments
+                .into_iter()
+                .map(|s| Segment {
+                    text: s.text.trim().to_string(),
+                    t0_ms: secs_to_ms(s.start),
+                    t1_ms: secs_to_ms(s.end),
+                    avg_logprob: s.avg_logprob.unwrap_or(0.0),
+                    no_speech_probability: s.no_speech_prob,
+                    tokens: Vec::new(),
+                })
+                .collect(),
+            None => vec![Segment {
+                text: self.text.trim().to_string(),
+                t0_ms: 0,
+                t1_ms: duration_ms,
+                avg_logprob: 0.0,
+                no_speech_probability: None,
+                tokens: Vec::new(),
+            }],
+        };
+        // OpenAI names the language ("english"), the local servers send the code
+        let language = self
+            .language
+            .and_then(|l| whisper_rs::get_lang_id(&l.to_lowercase()))
+            .and_then(whisper_rs::get_lang_str)
+            .map(str::to_string)
+            .or_else(|| (requested_language != AUTO_LANGUAGE).then(|| requested_language.to_string()));
+        Transcript::new(segments, language, None)
+    }
+}
+
+/// 16-bit PCM, what every server accepts without ffmpeg in between.
+fn encode_wav(audio: &[f32]) -> Result<Vec<u8>, String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: WHISPER_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::with_capacity(44 + audio.len() * 2));
+    let mut writer = hound::WavWriter::new(&mut cursor, spec).map_err(|e| e.to_string())?;
+    for sample in audio {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(sample).map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+/// Posts each utterance to an OpenAI compatible `/v1/audio/transcriptions` endpoint.
+pub struct ServerTranscriber {
+    options: ServerOptions,
+    client: reqwest::Client,
+}
+
+impl ServerTranscriber {
+    pub fn new(options: ServerOptions) -> Self {
+        Self {
+            options,
+            client: reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    async fn request(&self, decode_config: &DecodeConfig, prompt: &str, audio: &[f32]) -> Result<ServerResponse, String> {
+        let file = Part::bytes(encode_wav(audio)?)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")
+            .map_err(|e| e.to_string())?;
+        let mut form = Form::new()
+            .part("file", file)
+            .text(
+                "model",
+                self.options
+                    .model
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            )
+            .text("response_format", "verbose_json");
+        // the translation route always answers in English and takes no language
+        if !decode_config.translate && decode_config.language != AUTO_LANGUAGE {
+            form = form.text("language", decode_config.language.clone());
+        }
+        if !prompt.is_empty() {
+            form = form.text("prompt", prompt.to_string());
+        }
+        if let Some(temperature) = decode_config.options.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+
+        let mut request = self
+            .client
+            .post(self.options.url(decode_config.translate))
+            .timeout(Duration::from_secs(self.options.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)))
+            .multipart(form);
+        if let Some(key) = self.options.api_key.as_deref().filter(|k| !k.is_empty()) {
+            request = request.bearer_auth(key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Server request failed: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Server returned {}: {}", status, body.trim()));
+        }
+        response
+            .json::<ServerResponse>()
+            .await
+            .map_err(|e| format!("Invalid server response: {}", e))
+    }
+}
+
+impl Transcriber for ServerTranscriber {
+    fn transcribe(&self, decode_config: &DecodeConfig, prompt: &str, audio: &[f32]) -> Result<Transcript, String> {
+        // runs on the session's own worker, a plain thread, so blocking on the request is fine
+        let response = tauri::async_runtime::block_on(self.request(decode_config, prompt, audio))?;
+        let duration_ms = audio.len() as u64 * 1000 / WHISPER_SAMPLE_RATE as u64;
+        Ok(response.into_transcript(&decode_config.language, duration_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(endpoint: &str) -> ServerOptions {
+        ServerOptions {
+            endpoint: endpoint.to_string(),
+            api_key: None,
+            model: None,
+            timeout_secs: None,
+        }
+    }
+
+    #[test]
+    fn encodes_16k_mono_wav() {
+        let audio: Vec<f32> = (0..1600).map(|i| (i as f32 / 1600.0) - 0.5).collect();
+        let bytes = encode_wav(&audio).unwrap();
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().sample_rate, WHISPER_SAMPLE_RATE);
+        assert_eq!(reader.spec().channels, 1);
+        let decoded: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded.len(), audio.len());
+        assert_eq!(decoded[0], (-0.5 * i16::MAX as f32) as i16);
+    }
+
+    #[test]
+    fn translation_uses_its_own_route() {
+        let server = options("http://localhost:8000/v1/audio/transcriptions");
+        assert_eq!(server.url(false), "http://localhost:8000/v1/audio/transcriptions");
+        assert_eq!(server.url(true), "http://localhost:8000/v1/audio/translations");
+        assert_eq!(options("http://host/inference").url(true), "http://host/inference");
+    }
+
+    #[test]
+    fn rejects_endpoints_that_are_not_http() {
+        assert!(options("http://localhost:8000/v1/audio/transcriptions")
+            .validate()
+            .is_ok());
+        assert!(options("localhost:8000").validate().is_err());
+        assert!(options("file:///tmp/audio").validate().is_err());
+    }
+
+    #[test]
+    fn reads_verbose_json_segments() {
+        let body = r#"{
+            "task": "transcribe",
+            "language": "english",
+            "duration": 2.5,
+            "text": " Hello there. General Kenobi.",
+            "segments": [
+                {"id": 0, "start": 0.0, "end": 1.2, "text": " Hello there.", "avg_logprob": -0.2, "no_speech_prob": 0.01},
+                {"id": 1, "start": 1.2, "end": 2.5, "text": " General Kenobi.", "avg_logprob": -0.4, "no_speech_prob": 0.02}
+            ]
+        }"#;
+        let response: ServerResponse = serde_json::from_str(body).unwrap();
+        let transcript = response.into_transcript("en", 2500);
+        assert_eq!(transcript.text, "Hello there. General Kenobi.");
+        assert_eq!(transcript.segments[1].t0_ms, 1200);
+        assert_eq!(transcript.segments[1].no_speech_probability, Some(0.02));
+        assert_eq!(transcript.language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn plain_json_becomes_one_segment() {
+        let response: ServerResponse = serde_json::from_str(r#"{"text": "hello"}"#).unwrap();
+        let transcript = response.into_transcript(AUTO_LANGUAGE, 800);
+        assert_eq!(transcript.segments.len(), 1);
+        assert_eq!(transcript.segments[0].t1_ms, 800);
+        assert_eq!(transcript.language, None);
+    }
+}
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::Instant,
+};
+
+use crate::services::voice_commands::VoiceCommands;
+
+use super::{
+    decode::DecodeConfig,
+    filter::FilterOptions,
+    interim::InterimTracker,
+    level::LevelMeter,
+    preprocess::{PreprocessOptions, Preprocessor},
+    queue::{BacklogPolicy, BacklogState, InferenceQueue},
+    recorder::{Recorder, RecorderOptions},
+    server::{ServerOptions, ServerTranscriber},
+    vad::{Vad, VadConfig},
+};
+
+/// Session used by callers that do not name one.
+pub const DEFAULT_SESSION: &str = "default";
+
+/// How the audio stream is cut into utterances.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Segmentation {
+    /// Utterances end on silence.
+    Vad,
+    /// Fixed length chunks.
+    Timer,
+    /// Push-to-talk, `begin_utterance` and `end_utterance` mark the utterance.
+    Manual,
+}
+
+pub struct SessionConfig {
+    pub segmentation: Segmentation,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Input device name, None when audio is fed from outside.
+    pub device: Option<String>,
+    pub preprocess: PreprocessOptions,
+    pub vad: VadConfig,
+    pub decode: DecodeConfig,
+    pub filter: FilterOptions,
+    pub interim_interval_ms: Option<u64>,
+    /// Finals of this session that may wait for the worker before `backlog_policy` applies.
+    pub queue_capacity: usize,
+    pub backlog_policy: BacklogPolicy,
+    /// How often `whisper:level` is emitted, None turns the meter off.
+    pub level_interval_ms: Option<u64>,
+    /// Transcribe on an OpenAI compatible server instead of the loaded model.
+    pub server: Option<ServerOptions>,
+    /// `dir` is already the session folder.
+    pub recorder: Option<RecorderOptions>,
+    /// Matched against final results, shared with the other STT sources.
+    pub commands: VoiceCommands,
+}
+
+/// One audio source with its own buffer, VAD and decoding setup. Sessions without a server share the loaded model.
+pub struct Session {
+    pub id: String,
+    pub config: SessionConfig,
+    pub stop_sender: Mutex<Option<Sender<()>>>,
+    pub audio_buffer: Mutex<Vec<f32>>,
+    pub is_recording: Mutex<bool>,
+    /// Push-to-talk key is down, audio is only buffered while this is set.
+    pub utterance_open: Mutex<bool>,
+    /// Text of the last final result, fed back as context when enabled.
+    pub previous_text: Mutex<String>,
+    pub preprocessor: Mutex<Option<Preprocessor>>,
+    pub vad: Mutex<Vad>,
+    pub level_meter: Mutex<Option<LevelMeter>>,
+    pub server: Option<ServerTranscriber>,
+    /// Own queue of a server session, its worker waits on the network without holding up the loaded model.
+    pub queue: Option<Arc<InferenceQueue>>,
+    pub recorder: Option<Recorder>,
+    /// `received_frames` when the last interim window was queued.
+    pub last_interim_frame: Mutex<u64>,
+    pub interim_tracker: Mutex<InterimTracker>,
+    pub utterance_id: Mutex<u64>,
+    /// Frames received since recording started, used to place results on the timeline.
+    pub received_frames: Mutex<u64>,
+    /// Final results that made it out as `whisper:final`.
+    pub final_count: Mutex<u64>,
+    pub backlog: Mutex<BacklogState>,
+    pub started_at: Instant,
+}
+
+impl Session {
+    pub fn new(id: String, config: SessionConfig) -> Self {
+        let preprocessor = Preprocessor::new(&config.preprocess, config.sample_rate, config.channels);
+        let vad = Vad::new(config.vad.clone(), config.sample_rate, config.channels);
+        let level_meter = config
+            .level_interval_ms
+            .map(|interval_ms| LevelMeter::new(interval_ms, config.sample_rate, config.channels));
+        let server = config.server.clone().map(ServerTranscriber::new);
+        let queue = server.as_ref().map(|_| Arc::new(InferenceQueue::default()));
+        let recorder = config
+            .recorder
+            .as_ref()
+            .filter(|options| options.is_enabled())
+            .map(|options| Recorder::new(options, config.sample_rate));
+        Self {
+            id,
+            config,
+            stop_sender: Mutex::new(None),
+            audio_buffer: Mutex::new(Vec::new()),
+            is_recording: Mutex::new(true),
+            utterance_open: Mutex::new(false),
+            previous_text: Mutex::new(String::new()),
+            preprocessor: Mutex::new(preprocessor),
+            vad: Mutex::new(vad),
+            level_meter: Mutex::new(level_meter),
+            server,
+            queue,
+            recorder,
+            last_interim_frame: Mutex::new(0),
+            interim_tracker: Mutex::new(InterimTracker::default()),
+            utterance_id: Mutex::new(0),
+            received_frames: Mutex::new(0),
+            final_count: Mutex::new(0),
+            backlog: Mutex::new(BacklogState::default()),
+            started_at: Instant::now(),
+        }
+    }
+
+    pub fn info(&self) -> SessionInfo {
+        SessionInfo {
+            id: self.id.clone(),
+            segmentation: self.config.segmentation,
+            device: self.config.device.clone(),
+            sample_rate: self.config.sample_rate,
+            channels: self.config.channels,
+            language: self.config.decode.language.clone(),
+            server: self
+                .config
+                .server
+                .as_ref()
+                .map(|server| server.endpoint.clone()),
+        }
+    }
+
+    pub fn stats(&self, text: String) -> SessionStats {
+        let frames = *self.received_frames.lock().unwrap();
+        SessionStats {
+            id: self.id.clone(),
+            elapsed_ms: self.started_at.elapsed().as_millis() as u64,
+            audio_ms: frames * 1000 / self.config.sample_rate as u64,
+            utterances: *self.utterance_id.lock().unwrap(),
+            results: *self.final_count.lock().unwrap(),
+            text,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub segmentation: Segmentation,
+    pub device: Option<String>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub language: String,
+    /// Endpoint the session transcribes on, None for the loaded model.
+    pub server: Option<String>,
+}
+
+/// Payload of `whisper:stopped`.
+#[derive(Clone, Serialize)]
+pub struct SessionStats {
+    pub id: String,
+    /// Wall time since the session started.
+    pub elapsed_ms: u64,
+    /// Audio received, lower than `elapsed_ms` when a remote source stalled.
+    pub audio_ms: u64,
+    /// Utterances that were cut, including the ones too short to transcribe.
+    pub utterances: u64,
+    /// Utterances that produced a `whisper:final`.
+    pub results: u64,
+    /// Text of the last utterance, flushed when the session stopped.
+    pub text: String,
+}
@@ -0,0 +1,167 @@
+use serde::Deserialize;
+use std::{
+    fmt::Write as _,
+    fs::OpenOptions,
+    io::{ErrorKind, Write as _},
+    path::{Path, PathBuf},
+};
+
+use super::transcript::Segment;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    #[default]
+    Srt,
+    Vtt,
+    Txt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+            SubtitleFormat::Txt => "txt",
+        }
+    }
+}
+
+/// `hh:mm:ss` followed by the millisecond separator, SRT uses a comma and WebVTT a dot.
+fn timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+pub fn render(format: SubtitleFormat, segments: &[Segment]) -> String {
+    let mut out = String::new();
+    match format {
+        SubtitleFormat::Srt => {
+            for (i, segment) in segments.iter().enumerate() {
+                let _ = write!(
+                    out,
+                    "{}\n{} --> {}\n{}\n\n",
+                    i + 1,
+                    timestamp(segment.t0_ms, ','),
+                    timestamp(segment.t1_ms, ','),
+                    segment.text
+                );
+            }
+        }
+        SubtitleFormat::Vtt => {
+            out.push_str("WEBVTT\n\n");
+            for segment in segments {
+                let _ = write!(
+                    out,
+                    "{} --> {}\n{}\n\n",
+                    timestamp(segment.t0_ms, '.'),
+                    timestamp(segment.t1_ms, '.'),
+                    segment.text
+                );
+            }
+        }
+        SubtitleFormat::Txt => {
+            for segment in segments {
+                out.push_str(&segment.text);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Writes the captions next to `source` with the format's extension. An existing file is never replaced,
+/// the name gets a number instead, `talk (1).srt`. Returns the path written.
+pub fn write_next_to(source: &Path, format: SubtitleFormat, contents: &str) -> Result<PathBuf, String> {
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut output = source.with_extension(format.extension());
+    let mut n = 1;
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&output)
+        {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())
+                    .map_err(|e| e.to_string())?;
+                return Ok(output);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                output = source.with_file_name(format!("{} ({}).{}", stem, n, format.extension()));
+                n += 1;
+            }
+            Err(e) => return Err(format!("Failed to write {}: {}", output.display(), e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn segment(t0_ms: u64, t1_ms: u64, text: &str) -> Segment {
+        Segment {
+            text: text.to_string(),
+            t0_ms,
+            t1_ms,
+            avg_logprob: -0.2,
+            no_speech_probability: None,
+            tokens: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn timestamps_roll_over_at_each_unit() {
+        assert_eq!(timestamp(0, ','), "00:00:00,000");
+        assert_eq!(timestamp(999, ','), "00:00:00,999");
+        assert_eq!(timestamp(1000, ','), "00:00:01,000");
+        assert_eq!(timestamp(59_999, '.'), "00:00:59.999");
+        assert_eq!(timestamp(60_000, '.'), "00:01:00.000");
+        assert_eq!(timestamp(3_599_999, ','), "00:59:59,999");
+        assert_eq!(timestamp(3_600_000, ','), "01:00:00,000");
+        assert_eq!(timestamp(37_230_045, '.'), "10:20:30.045");
+    }
+
+    #[test]
+    fn renders_srt_and_vtt() {
+        let segments = [segment(1_500, 3_999, "Hello there."), segment(3_723_004, 3_725_000, "An hour later.")];
+        assert_eq!(
+            render(SubtitleFormat::Srt, &segments),
+            "1\n00:00:01,500 --> 00:00:03,999\nHello there.\n\n2\n01:02:03,004 --> 01:02:05,000\nAn hour later.\n\n"
+        );
+        assert_eq!(
+            render(SubtitleFormat::Vtt, &segments),
+            "WEBVTT\n\n00:00:01.500 --> 00:00:03.999\nHello there.\n\n01:02:03.004 --> 01:02:05.000\nAn hour later.\n\n"
+        );
+        assert_eq!(render(SubtitleFormat::Txt, &segments), "Hello there.\nAn hour later.\n");
+    }
+
+    #[test]
+    fn never_overwrites_existing_captions() {
+        let dir = std::env::temp_dir().join(format!("whisper-subtitles-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("talk.mp3");
+        fs::write(dir.join("talk.srt"), "mine").unwrap();
+
+        let first = write_next_to(&source, SubtitleFormat::Srt, "one").unwrap();
+        let second = write_next_to(&source, SubtitleFormat::Srt, "two").unwrap();
+        assert_eq!(first, dir.join("talk (1).srt"));
+        assert_eq!(second, dir.join("talk (2).srt"));
+        assert_eq!(fs::read_to_string(dir.join("talk.srt")).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "two");
+        assert_eq!(write_next_to(&source, SubtitleFormat::Vtt, "").unwrap(), dir.join("talk.vtt"));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,490 @@
+//! Runs the capture pipeline end to end against the mock transcriber.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::services::voice_commands::{VoiceCommand, VoiceCommandConfig, VoiceCommands};
+
+use super::{
+    decode::DecodeConfig,
+    filter::FilterOptions,
+    pipeline::{self, EventSink},
+    preprocess::PreprocessOptions,
+    queue::{self, BacklogPolicy, InferenceQueue},
+    recorder::RecorderOptions,
+    session::{Segmentation, Session, SessionConfig},
+    transcriber::MockTranscriber,
+    vad::VadConfig,
+};
+
+#[derive(Clone, Default)]
+struct RecordedEvents(Arc<Mutex<Vec<(String, Value)>>>);
+
+impl EventSink for RecordedEvents {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        let payload = serde_json::to_value(payload).unwrap();
+        self.0.lock().unwrap().push((event.to_string(), payload));
+    }
+}
+
+impl RecordedEvents {
+    fn named(&self, event: &str) -> Vec<Value> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| name == event)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+}
+
+struct Harness {
+    queue: Arc<InferenceQueue>,
+    events: RecordedEvents,
+    transcriber: Arc<MockTranscriber>,
+    session: Arc<Session>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Harness {
+    fn new(config: SessionConfig, transcriber: MockTranscriber) -> Self {
+        let queue = Arc::new(InferenceQueue::default());
+        let events = RecordedEvents::default();
+        let transcriber = Arc::new(transcriber);
+        let (worker_queue, worker_events, worker_transcriber) = (queue.clone(), events.clone(), transcriber.clone());
+        let worker = thread::spawn(move || pipeline::inference_worker(worker_events, &worker_queue, &*worker_transcriber));
+        Self {
+            queue,
+            events,
+            transcriber,
+            session: Arc::new(Session::new("test".to_string(), config)),
+            worker: Some(worker),
+        }
+    }
+
+    /// Feeds the samples in capture-callback-sized pieces.
+    fn feed(&self, samples: &[f32], callback_len: usize) {
+        for chunk in samples.chunks(callback_len) {
+            pipeline::process_audio_chunk(&self.queue, &self.session, &self.events, chunk.to_vec());
+        }
+    }
+
+    /// Waits until every queued final of the session went through the mock.
+    fn settle(&self) {
+        self.queue.drain(&self.session);
+    }
+
+    fn finals(&self) -> Vec<Value> {
+        self.events.named("whisper:final")
+    }
+}
+
+impl Drop for Harness {
+    /// Stops the worker so no test leaves a thread behind.
+    fn drop(&mut self) {
+        self.queue.close();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn config(segmentation: Segmentation, sample_rate: u32, channels: u16) -> SessionConfig {
+    SessionConfig {
+        segmentation,
+        sample_rate,
+        channels,
+        device: None,
+        preprocess: PreprocessOptions::default(),
+        vad: VadConfig {
+            silence_duration_ms: 500,
+            min_chunk_duration_ms: 500,
+            ..VadConfig::default()
+        },
+        decode: DecodeConfig::default(),
+        filter: FilterOptions::default(),
+        interim_interval_ms: None,
+        queue_capacity: queue::DEFAULT_CAPACITY,
+        backlog_policy: BacklogPolicy::DropOldest,
+        level_interval_ms: None,
+        server: None,
+        recorder: None,
+        commands: VoiceCommands::default(),
+    }
+}
+
+fn fixture(name: &str) -> (Vec<f32>, u32, u16) {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let mut reader = hound::WavReader::open(path).expect("missing fixture");
+    let spec = reader.spec();
+    let samples = reader
+        .samples::<i16>()
+        .map(|s| s.unwrap() as f32 / i16::MAX as f32)
+        .collect();
+    (samples, spec.sample_rate, spec.channels)
+}
+
+fn tone(rate: u32, secs: f32) -> Vec<f32> {
+    (0..(rate as f32 * secs) as usize)
+        .map(|i| 0.3 * (2.0 * PI * 220.0 * i as f32 / rate as f32).sin())
+        .collect()
+}
+
+fn ms(payload: &Value, field: &str) -> u64 {
+    payload[field].as_u64().unwrap()
+}
+
+#[test]
+fn vad_fixture_emits_one_final_per_utterance() {
+    let (samples, rate, channels) = fixture("vad_two_utterances.wav");
+    let harness = Harness::new(config(Segmentation::Vad, rate, channels), MockTranscriber::default());
+    harness.feed(&samples, 512);
+    harness.settle();
+
+    let finals = harness.finals();
+    assert_eq!(finals.len(), 2);
+    let calls = harness.transcriber.calls.lock().unwrap();
+    for (i, payload) in finals.iter().enumerate() {
+        assert_eq!(payload["session"], "test");
+        assert_eq!(payload["sequence"], i as u64);
+        assert_eq!(payload["text"], format!("{} ms", calls[i].0));
+        let duration_ms = ms(payload, "end_ms") - ms(payload, "start_ms");
+        assert!(calls[i].0.abs_diff(duration_ms) <= 1, "{} ms sent for {} ms", calls[i].0, duration_ms);
+    }
+    // 1.0 s of voice, plus pre-roll and hangover
+    assert!(calls[0].0 > 1300 && calls[0].0 < 2000, "first utterance lasted {} ms", calls[0].0);
+    assert!(ms(&finals[0], "end_ms") <= ms(&finals[1], "start_ms"));
+}
+
+#[test]
+fn stereo_48k_fixture_reaches_transcriber_as_16k_mono() {
+    let (samples, rate, channels) = fixture("vad_stereo_48k.wav");
+    let harness = Harness::new(config(Segmentation::Vad, rate, channels), MockTranscriber::default());
+    harness.feed(&samples, 1920);
+    harness.settle();
+
+    let finals = harness.finals();
+    assert_eq!(finals.len(), 1);
+    let calls = harness.transcriber.calls.lock().unwrap();
+    let duration_ms = ms(&finals[0], "end_ms") - ms(&finals[0], "start_ms");
+    assert!(calls[0].0.abs_diff(duration_ms) <= 1, "{} ms sent for {} ms", calls[0].0, duration_ms);
+}
+
+#[test]
+fn failed_inference_is_reported_for_the_utterance() {
+    let harness = Harness::new(
+        config(Segmentation::Timer, 16000, 1),
+        MockTranscriber::failing("Inference failed: out of memory"),
+    );
+    harness.feed(&tone(16000, 6.0), 1600);
+    harness.settle();
+
+    assert!(harness.finals().is_empty());
+    let errors = harness.events.named("whisper:error");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0]["session"], "test");
+    assert_eq!(errors[0]["sequence"], 0);
+    assert_eq!((ms(&errors[0], "start_ms"), ms(&errors[0], "end_ms")), (0, 5000));
+    assert_eq!(errors[0]["error"], "Inference failed: out of memory");
+}
+
+#[test]
+fn timer_segmentation_cuts_by_received_audio() {
+    let harness = Harness::new(config(Segmentation::Timer, 16000, 1), MockTranscriber::default());
+    harness.feed(&tone(16000, 12.0), 1600);
+    let last = pipeline::finish_session(&harness.queue, &harness.session, &harness.events).expect("2 s left over");
+    harness.settle();
+
+    let spans: Vec<(u64, u64)> = harness
+        .finals()
+        .iter()
+        .map(|payload| (ms(payload, "start_ms"), ms(payload, "end_ms")))
+        .collect();
+    assert_eq!(spans, vec![(0, 5000), (5000, 10000), (10000, 12000)]);
+    assert_eq!(last.blocking_recv().unwrap().unwrap().text, "2000 ms");
+}
+
+#[test]
+fn closed_queue_stops_the_worker_after_its_jobs() {
+    let mut harness = Harness::new(config(Segmentation::Timer, 16000, 1), MockTranscriber::default());
+    harness.feed(&tone(16000, 11.0), 1600);
+    harness.queue.close();
+    harness.worker.take().unwrap().join().unwrap();
+
+    assert_eq!(harness.finals().len(), 2);
+    assert!(!pipeline::run_next(&harness.events, &harness.queue, &*harness.transcriber));
+}
+
+#[test]
+fn manual_segmentation_transcribes_only_between_begin_and_end() {
+    let harness = Harness::new(config(Segmentation::Manual, 16000, 1), MockTranscriber::default());
+    harness.feed(&tone(16000, 1.0), 1600);
+    pipeline::begin_utterance(&harness.session).unwrap();
+    harness.feed(&tone(16000, 2.0), 1600);
+    let reply = pipeline::end_utterance(&harness.queue, &harness.session, &harness.events).unwrap();
+
+    let transcript = reply.blocking_recv().unwrap().unwrap();
+    assert_eq!((transcript.start_ms, transcript.end_ms), (1000, 3000));
+    assert_eq!(transcript.text, "2000 ms");
+    // closed again, nothing more is buffered
+    harness.feed(&tone(16000, 1.0), 1600);
+    assert!(harness.session.audio_buffer.lock().unwrap().is_empty());
+    assert!(pipeline::end_utterance(&harness.queue, &harness.session, &harness.events).is_err());
+}
+
+#[test]
+fn held_push_to_talk_is_cut_at_the_maximum_length() {
+    let harness = Harness::new(config(Segmentation::Manual, 16000, 1), MockTranscriber::default());
+    pipeline::begin_utterance(&harness.session).unwrap();
+    harness.feed(&tone(16000, 65.0), 1600);
+    let reply = pipeline::end_utterance(&harness.queue, &harness.session, &harness.events).unwrap();
+
+    assert_eq!(reply.blocking_recv().unwrap().unwrap().text, "5000 ms");
+    let texts: Vec<Value> = harness
+        .finals()
+        .iter()
+        .map(|payload| payload["text"].clone())
+        .collect();
+    assert_eq!(texts, ["30000 ms", "30000 ms", "5000 ms"]);
+}
+
+#[test]
+fn begin_utterance_needs_manual_segmentation() {
+    let harness = Harness::new(config(Segmentation::Vad, 16000, 1), MockTranscriber::default());
+    assert!(pipeline::begin_utterance(&harness.session).is_err());
+}
+
+#[test]
+fn filtered_results_are_reported_as_rejected() {
+    let (samples, rate, channels) = fixture("vad_two_utterances.wav");
+    let harness = Harness::new(
+        config(Segmentation::Vad, rate, channels),
+        MockTranscriber::with(|_| "Thanks for watching!".to_string()),
+    );
+    harness.feed(&samples, 512);
+    harness.settle();
+
+    assert!(harness.finals().is_empty());
+    let rejected = harness.events.named("whisper:rejected");
+    assert_eq!(rejected.len(), 2);
+    assert_eq!(rejected[1]["sequence"], 1);
+}
+
+#[test]
+fn previous_text_is_carried_into_the_prompt() {
+    let (samples, rate, channels) = fixture("vad_two_utterances.wav");
+    let mut config = config(Segmentation::Vad, rate, channels);
+    config.decode.options.carry_context = true;
+    let harness = Harness::new(config, MockTranscriber::with(|_| "hello there".to_string()));
+    harness.feed(&samples, 512);
+    harness.settle();
+
+    let calls = harness.transcriber.calls.lock().unwrap();
+    assert_eq!(calls.len(), 2);
+    assert!(!calls[0].1.contains("hello there"));
+    assert!(calls[1].1.contains("hello there"));
+}
+
+#[test]
+fn interim_windows_follow_received_audio() {
+    let mut config = config(Segmentation::Manual, 16000, 1);
+    config.interim_interval_ms = Some(500);
+    let harness = Harness::new(config, MockTranscriber::default());
+    pipeline::begin_utterance(&harness.session).unwrap();
+    harness.feed(&tone(16000, 1.0), 1600);
+
+    // the worker may skip the 500 ms window, the newest one always gets through
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !harness
+        .events
+        .named("whisper:interim")
+        .iter()
+        .any(|payload| payload["text"] == "1000 ms")
+    {
+        assert!(Instant::now() < deadline, "no interim for the full window");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(harness.finals().is_empty());
+}
+
+#[test]
+fn level_events_are_throttled_by_audio_time() {
+    let mut config = config(Segmentation::Vad, 16000, 1);
+    config.level_interval_ms = Some(100);
+    let harness = Harness::new(config, MockTranscriber::default());
+    harness.feed(&tone(16000, 1.0), 441);
+
+    let levels = harness.events.named("whisper:level");
+    assert_eq!(levels.len(), 10);
+    assert!(levels.iter().all(|payload| payload["session"] == "test"));
+}
+
+#[test]
+fn every_final_comes_with_metrics() {
+    let harness = Harness::new(config(Segmentation::Timer, 16000, 1), MockTranscriber::default());
+    harness.feed(&tone(16000, 10.0), 1600);
+    harness.settle();
+
+    let metrics = harness.events.named("whisper:metrics");
+    assert_eq!(metrics.len(), harness.finals().len());
+    for (i, payload) in metrics.iter().enumerate() {
+        assert_eq!(payload["sequence"], i as u64);
+        assert_eq!(ms(payload, "audio_ms"), 5000);
+        assert!(payload["rtf"].as_f64().unwrap() < 1.0);
+    }
+}
+
+#[test]
+fn recorder_keeps_what_whisper_heard() {
+    let dir = std::env::temp_dir().join(format!("whisper-pipeline-recorder-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let (samples, rate, channels) = fixture("vad_stereo_48k.wav");
+    let mut config = config(Segmentation::Vad, rate, channels);
+    config.recorder = Some(RecorderOptions {
+        save_utterances: true,
+        replay_minutes: 1,
+        dir: Some(dir.clone()),
+    });
+    let harness = Harness::new(config, MockTranscriber::default());
+    harness.feed(&samples, 1920);
+    harness.settle();
+
+    let calls = harness.transcriber.calls.lock().unwrap();
+    let sidecar = std::fs::read_to_string(dir.join("utterances.jsonl")).unwrap();
+    let record: Value = serde_json::from_str(sidecar.lines().next().unwrap()).unwrap();
+    assert_eq!(sidecar.lines().count(), 1);
+    assert_eq!(record["text"], format!("{} ms", calls[0].0));
+    let utterance = hound::WavReader::open(dir.join(record["file"].as_str().unwrap())).unwrap();
+    assert_eq!((utterance.spec().sample_rate, utterance.spec().channels), (16000, 1));
+    assert_eq!(utterance.duration() as u64 * 1000 / 16000, calls[0].0);
+
+    let replay_path = harness
+        .session
+        .recorder
+        .as_ref()
+        .unwrap()
+        .save_replay(None)
+        .unwrap();
+    let replay = hound::WavReader::open(replay_path).unwrap();
+    let fixture_ms = (samples.len() / channels as usize) as u64 * 1000 / rate as u64;
+    assert!((replay.duration() as u64 * 1000 / 16000).abs_diff(fixture_ms) <= 1);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn voice_commands_are_taken_out_of_finals() {
+    let mut config = config(Segmentation::Timer, 16000, 1);
+    config.commands.set(VoiceCommandConfig {
+        commands: vec![
+            VoiceCommand {
+                id: "captions_clear".to_string(),
+                phrase: "captions clear".to_string(),
+            },
+            VoiceCommand {
+                id: "mute_tts".to_string(),
+                phrase: "mute tts".to_string(),
+            },
+        ],
+        suppress: true,
+        ..VoiceCommandConfig::default()
+    });
+    let harness = Harness::new(
+        config,
+        MockTranscriber::with(|audio| match audio.len() * 1000 / 16000 {
+            5000 => "Caption's clear. Hello chat.".to_string(),
+            _ => "Mute TTS.".to_string(),
+        }),
+    );
+    harness.feed(&tone(16000, 7.0), 1600);
+    let last = pipeline::finish_session(&harness.queue, &harness.session, &harness.events).expect("2 s left over");
+    harness.settle();
+
+    let commands = harness.events.named("voice_command");
+    let ids: Vec<&str> = commands
+        .iter()
+        .map(|payload| payload["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["captions_clear", "mute_tts"]);
+    assert!(commands
+        .iter()
+        .all(|payload| payload["source"] == "whisper" && payload["session"] == "test"));
+    let finals = harness.finals();
+    assert_eq!(finals.len(), 1);
+    assert_eq!(finals[0]["text"], "Hello chat.");
+    assert_eq!(last.blocking_recv().unwrap().unwrap().text, "");
+}
+
+#[test]
+fn merged_utterances_answer_every_caller() {
+    let mut config = config(Segmentation::Manual, 16000, 1);
+    config.queue_capacity = 1;
+    config.backlog_policy = BacklogPolicy::Merge;
+    let harness = Harness::new(
+        config,
+        MockTranscriber::with(|audio| {
+            thread::sleep(Duration::from_millis(200));
+            format!("{} ms", audio.len() * 1000 / 16000)
+        }),
+    );
+    let utterance = || {
+        pipeline::begin_utterance(&harness.session).unwrap();
+        harness.feed(&tone(16000, 1.0), 1600);
+        pipeline::end_utterance(&harness.queue, &harness.session, &harness.events).unwrap()
+    };
+    let first = utterance();
+    // the worker is busy with the first one while the next two pile up
+    while harness.transcriber.calls.lock().unwrap().is_empty() {
+        thread::sleep(Duration::from_millis(5));
+    }
+    let (second, third) = (utterance(), utterance());
+
+    assert_eq!(first.blocking_recv().unwrap().unwrap().text, "1000 ms");
+    assert_eq!(second.blocking_recv().unwrap().unwrap().text, "2000 ms");
+    assert_eq!(third.blocking_recv().unwrap().unwrap().text, "2000 ms");
+    let backlog = harness.events.named("whisper:backlog");
+    assert!(backlog.iter().any(|payload| payload["merged"] == 1));
+}
+
+#[test]
+fn a_full_session_does_not_drop_utterances_of_another() {
+    let mut config = config(Segmentation::Manual, 16000, 1);
+    config.queue_capacity = 1;
+    let harness = Harness::new(
+        config,
+        MockTranscriber::with(|audio| {
+            thread::sleep(Duration::from_millis(100));
+            format!("{} ms", audio.len() * 1000 / 16000)
+        }),
+    );
+    let other = Arc::new(Session::new("other".to_string(), self::config(Segmentation::Manual, 16000, 1)));
+    let utterance = |session: &Arc<Session>| {
+        pipeline::begin_utterance(session).unwrap();
+        pipeline::process_audio_chunk(&harness.queue, session, &harness.events, tone(16000, 1.0));
+        pipeline::end_utterance(&harness.queue, session, &harness.events).unwrap()
+    };
+    let first = utterance(&harness.session);
+    while harness.transcriber.calls.lock().unwrap().is_empty() {
+        thread::sleep(Duration::from_millis(5));
+    }
+    let from_other = utterance(&other);
+    let dropped: Vec<_> = (0..2).map(|_| utterance(&harness.session)).collect();
+    let last = utterance(&harness.session);
+
+    assert!(first.blocking_recv().unwrap().is_ok());
+    assert_eq!(from_other.blocking_recv().unwrap().unwrap().text, "1000 ms");
+    assert!(dropped
+        .into_iter()
+        .all(|reply| reply.blocking_recv().is_err()));
+    assert!(last.blocking_recv().unwrap().is_ok());
+    let backlog = harness.events.named("whisper:backlog");
+    assert!(backlog.iter().all(|payload| payload["session"] == "test"));
+    assert_eq!(backlog.last().unwrap()["dropped"], 2);
+}
@@ -0,0 +1,133 @@
+use std::sync::{Arc, Mutex};
+use whisper_rs::{FullParams, WhisperContext};
+
+use super::{
+    decode::{DecodeConfig, AUTO_LANGUAGE},
+    transcript::{self, Transcript},
+};
+
+/// Speech recognition engine behind the capture pipeline.
+pub trait Transcriber: Send + Sync {
+    /// Transcribes 16 kHz mono audio, segment times are relative to its start.
+    fn transcribe(&self, decode_config: &DecodeConfig, prompt: &str, audio: &[f32]) -> Result<Transcript, String>;
+}
+
+/// Runs whichever model `load_model` put into the shared context.
+#[derive(Clone)]
+pub struct WhisperTranscriber {
+    ctx: Arc<Mutex<Option<WhisperContext>>>,
+}
+
+impl WhisperTranscriber {
+    pub fn new(ctx: Arc<Mutex<Option<WhisperContext>>>) -> Self {
+        Self { ctx }
+    }
+}
+
+impl Transcriber for WhisperTranscriber {
+    fn transcribe(&self, decode_config: &DecodeConfig, prompt: &str, resampled: &[f32]) -> Result<Transcript, String> {
+        let n_threads = decode_config.n_threads();
+        let mut ctx_guard = self.ctx.lock().map_err(|_| "Failed to lock ctx")?;
+        let ctx = ctx_guard.as_mut().ok_or("Model not loaded")?;
+
+        // Create state (ephemeral)
+        let mut w_state = ctx
+            .create_state()
+            .map_err(|e| format!("Failed to create state: {}", e))?;
+
+        let mut language_probability = None;
+        let language = if decode_config.language == AUTO_LANGUAGE {
+            w_state
+                .pcm_to_mel(resampled, n_threads as usize)
+                .map_err(|e| format!("Failed to compute mel: {}", e))?;
+            let (lang_id, probs) = w_state
+                .lang_detect(0, n_threads as usize)
+                .map_err(|e| format!("Language detection failed: {}", e))?;
+            language_probability = probs.get(lang_id as usize).copied();
+            whisper_rs::get_lang_str(lang_id).ok_or("Unknown language detected")?
+        } else {
+            decode_config.language.as_str()
+        };
+
+        let mut params = FullParams::new(decode_config.sampling_strategy());
+        params.set_n_threads(n_threads);
+        params.set_language(Some(language));
+        params.set_translate(decode_config.translate);
+        if !prompt.is_empty() {
+            params.set_initial_prompt(prompt);
+        }
+        if let Some(temperature) = decode_config.options.temperature {
+            params.set_temperature(temperature);
+        }
+        if let Some(temperature_inc) = decode_config.options.temperature_inc {
+            params.set_temperature_inc(temperature_inc);
+        }
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        params.set_token_timestamps(true);
+
+        w_state
+            .full(params, resampled)
+            .map_err(|e| format!("Inference failed: {}", e))?;
+
+        let segments = transcript::collect_segments(ctx, &w_state)?;
+        Ok(Transcript::new(segments, Some(language.to_string()), language_probability))
+    }
+}
+
+/// Answers without a model. The text is picked by `respond` from the audio, and every call is logged.
+#[cfg(test)]
+pub struct MockTranscriber {
+    respond: Box<dyn Fn(&[f32]) -> Result<String, String> + Send + Sync>,
+    /// Length in ms and prompt of each request.
+    pub calls: Mutex<Vec<(u64, String)>>,
+}
+
+#[cfg(test)]
+impl Default for MockTranscriber {
+    /// Says how long the audio was, "1520 ms".
+    fn default() -> Self {
+        Self::with(|audio| format!("{} ms", audio.len() as u64 * 1000 / super::dsp::WHISPER_SAMPLE_RATE as u64))
+    }
+}
+
+#[cfg(test)]
+impl MockTranscriber {
+    pub fn with(respond: impl Fn(&[f32]) -> String + Send + Sync + 'static) -> Self {
+        Self {
+            respond: Box::new(move |audio| Ok(respond(audio))),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Fails every request with `error`.
+    pub fn failing(error: &str) -> Self {
+        let error = error.to_string();
+        Self {
+            respond: Box::new(move |_| Err(error.clone())),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Transcriber for MockTranscriber {
+    fn transcribe(&self, _decode_config: &DecodeConfig, prompt: &str, audio: &[f32]) -> Result<Transcript, String> {
+        let duration_ms = audio.len() as u64 * 1000 / super::dsp::WHISPER_SAMPLE_RATE as u64;
+        self.calls
+            .lock()
+            .unwrap()
+            .push((duration_ms, prompt.to_string()));
+        let segment = transcript::Segment {
+            text: (self.respond)(audio)?,
+            t0_ms: 0,
+            t1_ms: duration_ms,
+            avg_logprob: -0.1,
+            no_speech_probability: None,
+            tokens: Vec::new(),
+        };
+        Ok(Transcript::new(vec![segment], Some("en".to_string()), None))
+    }
+}
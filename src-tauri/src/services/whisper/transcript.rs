@@ -0,0 +1,129 @@
+use serde::Serialize;
+use whisper_rs::{WhisperContext, WhisperState};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Token {
+    pub text: String,
+    pub probability: f32,
+    pub t0_ms: u64,
+    pub t1_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Segment {
+    pub text: String,
+    pub t0_ms: u64,
+    pub t1_ms: u64,
+    pub avg_logprob: f32,
+    /// Only reported by a `server` engine. whisper-rs 0.11 has no getter for the no-speech probability of a segment,
+    /// so local results leave the field out of the payload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_speech_probability: Option<f32>,
+    pub tokens: Vec<Token>,
+}
+
+/// Payload of `whisper:final`. Times are milliseconds since recording started.
+#[derive(Clone, Debug, Serialize)]
+pub struct Transcript {
+    /// Session the audio came from, empty for file transcription.
+    pub session: String,
+    pub sequence: u64,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+    pub segments: Vec<Segment>,
+    pub language: Option<String>,
+    pub language_probability: Option<f32>,
+}
+
+impl Transcript {
+    pub fn new(segments: Vec<Segment>, language: Option<String>, language_probability: Option<f32>) -> Self {
+        let mut transcript = Self {
+            session: String::new(),
+            sequence: 0,
+            start_ms: 0,
+            end_ms: 0,
+            text: String::new(),
+            segments,
+            language,
+            language_probability,
+        };
+        transcript.rebuild_text();
+        transcript
+    }
+
+    pub fn rebuild_text(&mut self) {
+        self.text = self
+            .segments
+            .iter()
+            .map(|s| s.text.trim())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    /// Places the transcript on the recording timeline, segment times are shifted along.
+    pub fn at(mut self, sequence: u64, start_ms: u64, end_ms: u64) -> Self {
+        self.sequence = sequence;
+        self.start_ms = start_ms;
+        self.end_ms = end_ms;
+        for segment in &mut self.segments {
+            segment.t0_ms += start_ms;
+            segment.t1_ms += start_ms;
+            for token in &mut segment.tokens {
+                token.t0_ms += start_ms;
+                token.t1_ms += start_ms;
+            }
+        }
+        self
+    }
+}
+
+fn centis_to_ms(t: i64) -> u64 {
+    t.max(0) as u64 * 10
+}
+
+/// Reads the segments of a finished `full` run, special tokens are left out.
+pub fn collect_segments(ctx: &WhisperContext, w_state: &WhisperState) -> Result<Vec<Segment>, String> {
+    let token_eot = ctx.token_eot();
+    let num_segments = w_state.full_n_segments().map_err(|e| e.to_string())?;
+    let mut segments = Vec::with_capacity(num_segments as usize);
+    for i in 0..num_segments {
+        let text = w_state
+            .full_get_segment_text(i)
+            .map_err(|e| e.to_string())?;
+        let t0 = w_state.full_get_segment_t0(i).map_err(|e| e.to_string())?;
+        let t1 = w_state.full_get_segment_t1(i).map_err(|e| e.to_string())?;
+
+        let num_tokens = w_state.full_n_tokens(i).map_err(|e| e.to_string())?;
+        let mut tokens = Vec::with_capacity(num_tokens as usize);
+        let mut logprob_sum = 0.0;
+        for j in 0..num_tokens {
+            let data = w_state
+                .full_get_token_data(i, j)
+                .map_err(|e| e.to_string())?;
+            if data.id >= token_eot {
+                continue;
+            }
+            logprob_sum += data.plog;
+            tokens.push(Token {
+                text: w_state.full_get_token_text(i, j).unwrap_or_default(),
+                probability: data.p,
+                t0_ms: centis_to_ms(data.t0),
+                t1_ms: centis_to_ms(data.t1),
+            });
+        }
+        let avg_logprob = if tokens.is_empty() { 0.0 } else { logprob_sum / tokens.len() as f32 };
+
+        segments.push(Segment {
+            text: text.trim().to_string(),
+            t0_ms: centis_to_ms(t0),
+            t1_ms: centis_to_ms(t1),
+            avg_logprob,
+            // not exposed by whisper-rs
+            no_speech_probability: None,
+            tokens,
+        });
+    }
+    Ok(segments)
+}
@@ -0,0 +1,436 @@
+use std::collections::VecDeque;
+
+use super::dsp::{calculate_rms_db, downmix};
+
+/// Margin the frame energy has to clear above the tracked noise floor.
+const NOISE_MARGIN_DB: f32 = 10.0;
+/// Per-frame smoothing towards a louder floor, kept slow so a word that starts does not drag it up.
+/// The floor only moves between utterances.
+const NOISE_FLOOR_RISE: f32 = 0.01;
+/// Per-frame smoothing towards a quieter floor.
+const NOISE_FLOOR_FALL: f32 = 0.2;
+/// Zero crossings per sample above which a frame sounds like hiss rather than voice.
+const MAX_SPEECH_ZCR: f32 = 0.35;
+/// Extra energy a noise-like frame needs before it counts as speech.
+const NOISY_FRAME_MARGIN_DB: f32 = 6.0;
+
+#[derive(Clone, Debug)]
+pub struct VadConfig {
+    pub silence_threshold_db: f32,
+    /// Hangover, how long speech has to stay quiet before the utterance ends.
+    pub silence_duration_ms: u64,
+    /// Utterances with less voiced time than this, from the onset to the last speech frame, are treated as clicks and dropped.
+    pub min_chunk_duration_ms: u64,
+    /// Analysis frame length, 10, 20 or 30 ms.
+    pub frame_ms: u64,
+    /// How long speech has to last before an utterance starts.
+    pub attack_ms: u64,
+    /// Audio from before the detected onset that is prepended to each utterance.
+    pub pre_roll_ms: u64,
+    /// Longer speech is cut here and goes on in a new utterance, Whisper only looks at 30 s at a time.
+    pub max_utterance_ms: u64,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            silence_threshold_db: -40.0,
+            silence_duration_ms: 1500,
+            min_chunk_duration_ms: 250,
+            frame_ms: 20,
+            attack_ms: 60,
+            pre_roll_ms: 300,
+            max_utterance_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VadEvent {
+    Start,
+    /// Interleaved samples belonging to the current utterance.
+    Audio(Vec<f32>),
+    End,
+    /// The utterance ended with too little voiced time, its audio should be dropped.
+    Cancel,
+}
+
+enum Phase {
+    Silence,
+    Attack {
+        speech_frames: u64,
+        audio: Vec<f32>,
+    },
+    Speech {
+        silent_frames: u64,
+        /// Frames since the onset.
+        frames: u64,
+        /// Frames from the onset to the last speech frame.
+        voiced_frames: u64,
+        /// Interleaved samples in the utterance, pre-roll included.
+        len: usize,
+        /// Started where a too long utterance was cut, any speech in it counts.
+        continued: bool,
+    },
+}
+
+fn zero_crossing_rate(samples: &[f32]) -> f32 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let crossings = samples
+        .windows(2)
+        .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+        .count();
+    crossings as f32 / (samples.len() - 1) as f32
+}
+
+/// Voice activity detector working on fixed-size frames, independent of the capture callback size.
+pub struct Vad {
+    config: VadConfig,
+    channels: usize,
+    /// Interleaved samples per frame.
+    frame_len: usize,
+    pending: Vec<f32>,
+    pre_roll: VecDeque<f32>,
+    pre_roll_len: usize,
+    max_utterance_len: usize,
+    noise_floor_db: f32,
+    phase: Phase,
+}
+
+impl Vad {
+    pub fn new(config: VadConfig, sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        let frame_ms = config.frame_ms.clamp(10, 30);
+        let frame_len = (sample_rate as u64 * frame_ms / 1000) as usize * channels;
+        let pre_roll_len = (sample_rate as u64 * config.pre_roll_ms / 1000) as usize * channels;
+        let max_utterance_len = (sample_rate as u64 * config.max_utterance_ms / 1000) as usize * channels;
+        let noise_floor_db = config.silence_threshold_db - NOISE_MARGIN_DB;
+        Self {
+            config: VadConfig { frame_ms, ..config },
+            channels,
+            frame_len: frame_len.max(channels),
+            pending: Vec::new(),
+            pre_roll: VecDeque::with_capacity(pre_roll_len),
+            pre_roll_len,
+            max_utterance_len,
+            noise_floor_db,
+            phase: Phase::Silence,
+        }
+    }
+
+    pub fn in_speech(&self) -> bool {
+        matches!(self.phase, Phase::Speech { .. })
+    }
+
+    /// Feeds interleaved samples of any length, returns what happened in the completed frames.
+    pub fn push(&mut self, data: &[f32]) -> Vec<VadEvent> {
+        self.pending.extend_from_slice(data);
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while self.pending.len() - offset >= self.frame_len {
+            let frame = self.pending[offset..offset + self.frame_len].to_vec();
+            offset += self.frame_len;
+            self.process_frame(frame, &mut events);
+        }
+        self.pending.drain(..offset);
+        events
+    }
+
+    /// Returns whether the frame is speech and its energy.
+    fn classify(&self, frame: &[f32]) -> (bool, f32) {
+        let mono = downmix(frame, self.channels as u16);
+        let energy_db = calculate_rms_db(&mono);
+        let zcr = zero_crossing_rate(&mono);
+
+        let threshold = self
+            .config
+            .silence_threshold_db
+            .max(self.noise_floor_db + NOISE_MARGIN_DB);
+        let speech = if zcr > MAX_SPEECH_ZCR {
+            energy_db > threshold + NOISY_FRAME_MARGIN_DB
+        } else {
+            energy_db > threshold
+        };
+        (speech, energy_db)
+    }
+
+    fn track_noise_floor(&mut self, energy_db: f32) {
+        let rate = if energy_db < self.noise_floor_db {
+            NOISE_FLOOR_FALL
+        } else {
+            NOISE_FLOOR_RISE
+        };
+        self.noise_floor_db += (energy_db - self.noise_floor_db) * rate;
+    }
+
+    fn push_pre_roll(&mut self, frame: &[f32]) {
+        self.pre_roll.extend(frame.iter().copied());
+        let excess = self.pre_roll.len().saturating_sub(self.pre_roll_len);
+        self.pre_roll.drain(..excess);
+    }
+
+    fn process_frame(&mut self, frame: Vec<f32>, events: &mut Vec<VadEvent>) {
+        let (speech, energy_db) = self.classify(&frame);
+        let frame_ms = self.config.frame_ms;
+        // speech held at a steady level would otherwise become the floor and cut itself off
+        if matches!(self.phase, Phase::Silence) {
+            self.track_noise_floor(energy_db);
+        }
+
+        match std::mem::replace(&mut self.phase, Phase::Silence) {
+            Phase::Silence if speech => {
+                self.phase = Phase::Attack {
+                    speech_frames: 0,
+                    audio: Vec::new(),
+                };
+                self.process_attack(frame, speech, events);
+            }
+            Phase::Silence => self.push_pre_roll(&frame),
+            Phase::Attack { speech_frames, audio } => {
+                self.phase = Phase::Attack { speech_frames, audio };
+                self.process_attack(frame, speech, events);
+            }
+            Phase::Speech {
+                silent_frames,
+                frames,
+                voiced_frames,
+                len,
+                continued,
+            } => {
+                let frames = frames + 1;
+                let (silent_frames, voiced_frames) = if speech { (0, frames) } else { (silent_frames + 1, voiced_frames) };
+                let len = len + frame.len();
+                push_audio(events, frame);
+                let voiced = voiced_frames > 0 && (continued || voiced_frames * frame_ms >= self.config.min_chunk_duration_ms);
+                let end = if voiced { VadEvent::End } else { VadEvent::Cancel };
+                if silent_frames * frame_ms >= self.config.silence_duration_ms {
+                    events.push(end);
+                } else if len >= self.max_utterance_len {
+                    events.push(end);
+                    events.push(VadEvent::Start);
+                    self.phase = Phase::Speech {
+                        silent_frames,
+                        frames: 0,
+                        voiced_frames: 0,
+                        len: 0,
+                        continued: true,
+                    };
+                } else {
+                    self.phase = Phase::Speech {
+                        silent_frames,
+                        frames,
+                        voiced_frames,
+                        len,
+                        continued,
+                    };
+                }
+            }
+        }
+    }
+
+    fn process_attack(&mut self, frame: Vec<f32>, speech: bool, events: &mut Vec<VadEvent>) {
+        let Phase::Attack { speech_frames, mut audio } = std::mem::replace(&mut self.phase, Phase::Silence) else {
+            return;
+        };
+        audio.extend_from_slice(&frame);
+        if !speech {
+            // false start, the frames become pre-roll again
+            self.push_pre_roll(&audio);
+            return;
+        }
+        let speech_frames = speech_frames + 1;
+        if speech_frames * self.config.frame_ms >= self.config.attack_ms {
+            let mut utterance: Vec<f32> = self.pre_roll.drain(..).collect();
+            utterance.extend_from_slice(&audio);
+            events.push(VadEvent::Start);
+            let len = utterance.len();
+            push_audio(events, utterance);
+            self.phase = Phase::Speech {
+                silent_frames: 0,
+                frames: speech_frames,
+                voiced_frames: speech_frames,
+                len,
+                continued: false,
+            };
+        } else {
+            self.phase = Phase::Attack { speech_frames, audio };
+        }
+    }
+}
+
+/// Appends audio to the last event when possible so callers get one buffer per callback.
+fn push_audio(events: &mut Vec<VadEvent>, samples: Vec<f32>) {
+    if let Some(VadEvent::Audio(audio)) = events.last_mut() {
+        audio.extend_from_slice(&samples);
+    } else {
+        events.push(VadEvent::Audio(samples));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn fixture(name: &str) -> (Vec<f32>, u32, u16) {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let mut reader = hound::WavReader::open(path).expect("missing fixture");
+        let spec = reader.spec();
+        let samples = reader
+            .samples::<i16>()
+            .map(|s| s.unwrap() as f32 / i16::MAX as f32)
+            .collect();
+        (samples, spec.sample_rate, spec.channels)
+    }
+
+    fn test_config() -> VadConfig {
+        VadConfig {
+            silence_duration_ms: 500,
+            min_chunk_duration_ms: 300,
+            ..VadConfig::default()
+        }
+    }
+
+    /// Runs the detector in callback-sized pieces and returns the utterances it produced.
+    fn utterances(vad: &mut Vad, samples: &[f32], callback_len: usize) -> Vec<Vec<f32>> {
+        let mut utterances = Vec::new();
+        let mut current: Option<Vec<f32>> = None;
+        for chunk in samples.chunks(callback_len) {
+            for event in vad.push(chunk) {
+                match event {
+                    VadEvent::Start => current = Some(Vec::new()),
+                    VadEvent::Audio(audio) => current
+                        .as_mut()
+                        .expect("audio outside utterance")
+                        .extend(audio),
+                    VadEvent::End => utterances.push(current.take().expect("end without start")),
+                    VadEvent::Cancel => current = None,
+                }
+            }
+        }
+        utterances
+    }
+
+    fn tone(rate: u32, secs: f32, amplitude: f32) -> Vec<f32> {
+        (0..(rate as f32 * secs) as usize)
+            .map(|i| amplitude * (2.0 * PI * 220.0 * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn detects_both_utterances_in_fixture() {
+        let (samples, rate, channels) = fixture("vad_two_utterances.wav");
+        let mut vad = Vad::new(test_config(), rate, channels);
+        let found = utterances(&mut vad, &samples, 512);
+        assert_eq!(found.len(), 2);
+        // 1.0 s of voice, plus pre-roll and hangover
+        let secs = found[0].len() as f32 / rate as f32;
+        assert!(secs > 1.3 && secs < 2.0, "first utterance lasted {}s", secs);
+    }
+
+    #[test]
+    fn detects_stereo_48k_fixture() {
+        let (samples, rate, channels) = fixture("vad_stereo_48k.wav");
+        let mut vad = Vad::new(test_config(), rate, channels);
+        let found = utterances(&mut vad, &samples, 1920);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].len() % 2, 0);
+    }
+
+    #[test]
+    fn callback_size_does_not_change_segmentation() {
+        let (samples, rate, channels) = fixture("vad_two_utterances.wav");
+        let small = utterances(&mut Vad::new(test_config(), rate, channels), &samples, 128);
+        let large = utterances(&mut Vad::new(test_config(), rate, channels), &samples, 4096);
+        assert_eq!(small.len(), large.len());
+        for (a, b) in small.iter().zip(large.iter()) {
+            assert_eq!(a.len(), b.len());
+        }
+    }
+
+    #[test]
+    fn prepends_pre_roll_to_utterance() {
+        let rate = 16000;
+        let mut samples = vec![0.0; rate as usize];
+        samples.extend(tone(rate, 0.5, 0.3));
+        samples.extend(vec![0.0; rate as usize]);
+        let mut vad = Vad::new(test_config(), rate, 1);
+        let found = utterances(&mut vad, &samples, 480);
+        assert_eq!(found.len(), 1);
+        // the first 300 ms are the silent pre-roll, the tone follows
+        let pre_roll = (rate as u64 * test_config().pre_roll_ms / 1000) as usize;
+        assert!(found[0][..pre_roll].iter().all(|s| *s == 0.0));
+        assert!(found[0][pre_roll..pre_roll + 480]
+            .iter()
+            .any(|s| s.abs() > 0.1));
+    }
+
+    #[test]
+    fn ignores_clicks_shorter_than_attack() {
+        let rate = 16000;
+        let mut samples = vec![0.0; rate as usize];
+        samples.extend(tone(rate, 0.02, 0.5));
+        samples.extend(vec![0.0; rate as usize]);
+        let mut vad = Vad::new(test_config(), rate, 1);
+        assert!(utterances(&mut vad, &samples, 480).is_empty());
+    }
+
+    #[test]
+    fn holds_steady_speech_for_its_whole_length() {
+        let rate = 16000;
+        let mut samples = vec![0.0; rate as usize];
+        samples.extend(tone(rate, 10.0, 0.3));
+        samples.extend(vec![0.0; rate as usize]);
+        let mut vad = Vad::new(test_config(), rate, 1);
+        let found = utterances(&mut vad, &samples, 480);
+        assert_eq!(found.len(), 1);
+        // pre-roll, 10 s of tone and the hangover
+        let secs = found[0].len() as f32 / rate as f32;
+        assert!(secs > 10.7 && secs < 10.9, "utterance lasted {}s", secs);
+    }
+
+    #[test]
+    fn default_config_keeps_a_short_answer() {
+        // about as long as a "yes" or "no"
+        let rate = 16000;
+        let mut samples = vec![0.0; rate as usize];
+        samples.extend(tone(rate, 0.4, 0.3));
+        samples.extend(vec![0.0; 2 * rate as usize]);
+        let mut vad = Vad::new(VadConfig::default(), rate, 1);
+        assert_eq!(utterances(&mut vad, &samples, 480).len(), 1);
+    }
+
+    #[test]
+    fn drops_bursts_with_little_voiced_time() {
+        let rate = 16000;
+        let mut samples = vec![0.0; rate as usize];
+        samples.extend(tone(rate, 0.2, 0.5));
+        samples.extend(vec![0.0; rate as usize]);
+        let mut vad = Vad::new(test_config(), rate, 1);
+        let mut events = Vec::new();
+        for chunk in samples.chunks(480) {
+            events.extend(vad.push(chunk));
+        }
+        assert!(events.contains(&VadEvent::Start));
+        assert_eq!(events.last(), Some(&VadEvent::Cancel));
+        assert!(!events.contains(&VadEvent::End));
+    }
+
+    #[test]
+    fn cuts_speech_longer_than_the_maximum() {
+        let rate = 16000;
+        let mut samples = vec![0.0; rate as usize];
+        samples.extend(tone(rate, 65.0, 0.3));
+        samples.extend(vec![0.0; rate as usize]);
+        let mut vad = Vad::new(test_config(), rate, 1);
+        let found = utterances(&mut vad, &samples, 480);
+        let secs: Vec<f32> = found.iter().map(|u| u.len() as f32 / rate as f32).collect();
+        assert_eq!(secs.len(), 3, "{:?}", secs);
+        assert_eq!(&secs[..2], [30.0, 30.0]);
+        // pre-roll, 65 s of tone and the hangover, nothing lost at the cuts
+        let total: f32 = secs.iter().sum();
+        assert!(total > 65.7 && total < 65.9, "{:?}", secs);
+    }
+}
@@ -34,6 +34,8 @@ export const BackendSchema = z.object({
     start: zSafe(z.string(), ""),
     muteMic: zSafe(z.string(), ""),
     muteSound: zSafe(z.string(), ""),
+    beginUtterance: zSafe(z.string(), ""),
+    endUtterance: zSafe(z.string(), ""),
   }).default({}),
   services: z.object({
     vrc: zodServiceSchemaFactory(Service_VRC_Schema).default({}),
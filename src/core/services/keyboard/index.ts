@@ -76,6 +76,13 @@ class Service_Keyboard implements IServiceInterface {
     if (shortcut === "bgInput") {
       this.startBackgroundInput();
     }
+    // whisper push-to-talk, only sessions started in manual segmentation accept these
+    if (shortcut === "beginUtterance") {
+      invoke("plugin:whisper|begin_utterance").catch(() => {});
+    }
+    if (shortcut === "endUtterance") {
+      invoke("plugin:whisper|end_utterance").catch(() => {});
+    }
   }
 
   // start/restart background timer
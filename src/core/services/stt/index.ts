@@ -115,10 +115,24 @@ class Service_STT implements IServiceInterface, ISTTReceiver {
     });
   }
 
+  updateVoiceCommands() {
+    invoke("plugin:voice_commands|set_voice_commands", {
+      config: {
+        commands: Object.entries(this.data.voiceCommands).map(([phrase, id]) => ({ id, phrase })),
+        threshold: (parseFloat(this.data.voiceCommandsThreshold) || 80) / 100,
+        suppress: this.data.voiceCommandsSuppress,
+      }
+    }).catch(e => console.error("[STT] Failed to set voice commands", e));
+  }
+
   async init() {
     this.updateReplacementsCache();
     this.eventDisposers.push(subscribeKey(this.data, "replaceWords", () => this.updateReplacementsCache()));
     this.eventDisposers.push(subscribeKey(this.data, "replaceWordsIgnoreCase", () => this.updateReplacementsCache()));
+    this.updateVoiceCommands();
+    this.eventDisposers.push(subscribeKey(this.data, "voiceCommands", () => this.updateVoiceCommands()));
+    this.eventDisposers.push(subscribeKey(this.data, "voiceCommandsThreshold", () => this.updateVoiceCommands()));
+    this.eventDisposers.push(subscribeKey(this.data, "voiceCommandsSuppress", () => this.updateVoiceCommands()));
 
     const unsubStream = window.ApiShared.pubsub.subscribe("stream.on_ended", () => {
       if (this.data.stopWithStream && this.serviceState.status === ServiceNetworkState.connected) {
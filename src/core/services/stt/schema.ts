@@ -22,6 +22,10 @@ export const Service_STT_Schema = z.object({
   replaceWords: zSafe(z.record(z.coerce.string(), z.coerce.string()), {}),
   replaceWordsIgnoreCase: zSafe(z.coerce.boolean(), false),
   replaceWordsPreserveCase: zSafe(z.coerce.boolean(), false),
+  // phrase -> command id
+  voiceCommands: zSafe(z.record(z.coerce.string(), z.coerce.string()), {}),
+  voiceCommandsThreshold: zSafe(zStringNumber(), "80"),
+  voiceCommandsSuppress: zSafe(z.coerce.boolean(), false),
   native: z.object({
     language_group: zSafe(z.coerce.string(), ""),
     language: zSafe(z.coerce.string(), ""),
@@ -45,10 +49,32 @@ export const Service_STT_Schema = z.object({
   }).default({}),
   whisper: z.object({
     model: zSafe(z.coerce.string(), "base.en"),
+    device: zSafe(z.coerce.string(), "default"),
+    language: zSafe(z.coerce.string(), "en"),
+    translate: zSafe(z.coerce.boolean(), false),
+    server: zSafe(z.coerce.boolean(), false),
+    serverEndpoint: zSafe(z.coerce.string(), "http://localhost:8000/v1/audio/transcriptions"),
+    serverModel: zSafe(z.coerce.string(), ""),
+    serverKey: zSafe(z.coerce.string(), ""),
     vadEnabled: zSafe(z.coerce.boolean(), true),
+    pushToTalk: zSafe(z.coerce.boolean(), false),
+    highPass: zSafe(z.coerce.boolean(), false),
+    noiseSuppression: zSafe(z.coerce.boolean(), false),
+    noiseGate: zSafe(z.coerce.boolean(), false),
+    agc: zSafe(z.coerce.boolean(), false),
     silenceThresholdDb: zSafe(zStringNumber(), "-40"),
     silenceDurationMs: zSafe(zStringNumber(), "1500"),
-    minChunkDurationMs: zSafe(zStringNumber(), "1000"),
+    minChunkDurationMs: zSafe(zStringNumber(), "250"),
+    interim: zSafe(z.coerce.boolean(), true),
+    interimIntervalMs: zSafe(zStringNumber(), "500"),
+    initialPrompt: zSafe(z.coerce.string(), ""),
+    vocabulary: zSafe(z.coerce.string(), ""),
+    carryContext: zSafe(z.coerce.boolean(), false),
+    beamSize: zSafe(zStringNumber(), "1"),
+    threads: zSafe(zStringNumber(), "4"),
+    unloadAfterMinutes: zSafe(zStringNumber(), "10"),
+    saveUtterances: zSafe(z.coerce.boolean(), false),
+    replayMinutes: zSafe(zStringNumber(), "0"),
   }).default({}),
   deepgram: z.object({
     device: zSafe(z.coerce.string(), "default"),
@@ -5,7 +5,8 @@ import { STT_State } from "../schema";
 
 export class STT_WhisperService implements ISpeechRecognitionService {
     private unlistenProgress?: UnlistenFn;
-    private unlistenPartial?: UnlistenFn;
+    private unlistenFinal?: UnlistenFn;
+    private unlistenInterim?: UnlistenFn;
     private isRecording = false;
     private accumulatedText = "";
 
@@ -24,49 +25,84 @@ export class STT_WhisperService implements ISpeechRecognitionService {
                 this.receiver.onInterim(`Downloading ${payload.file}: ${payload.progress.toFixed(0)}%...`);
             });
 
-            console.log("[Whisper] Ensuring dependencies...");
-            await invoke("plugin:whisper|ensure_dependencies");
+            await invoke("plugin:whisper|set_idle_timeout", { minutes: parseInt(params.whisper.unloadAfterMinutes) || null });
+
+            // the server brings its own model
+            if (!params.whisper.server) {
+                console.log("[Whisper] Ensuring dependencies...");
+                await invoke("plugin:whisper|ensure_dependencies", { model: params.whisper.model });
+            }
 
             if (this.unlistenProgress) {
                 this.unlistenProgress();
                 this.unlistenProgress = undefined;
             }
 
-            // IMPORTANT: Set up partial result listener BEFORE starting recording
+            // IMPORTANT: Set up result listeners BEFORE starting recording
             // to ensure we don't miss any events from early chunks
-            console.log("[Whisper] Setting up partial result listener...");
-            this.unlistenPartial = await listen("whisper:partial_result", (event) => {
-                let text = event.payload as string;
-                // console.log("[Whisper] Partial result received:", text);
-
-                // Filter out blank audio tokens and other non-speech sounds (e.g. [CHIRPING], (water splashing))
-                text = text.replace(/(\[[^\]]+\]|\([^\)]+\))/g, "").trim();
-
+            console.log("[Whisper] Setting up result listeners...");
+            this.unlistenInterim = await listen("whisper:interim", (event) => {
+                const payload = event.payload as { text: string; stable: string; unstable: string };
+                const text = payload.text.trim();
+                if (text) {
+                    this.receiver.onInterim(text);
+                }
+            });
+            this.unlistenFinal = await listen("whisper:final", (event) => {
+                const payload = event.payload as { text: string; language?: string; language_probability?: number };
+                // sound tags like [BLANK_AUDIO] are already stripped by the backend filter
+                const text = payload.text.trim();
                 if (text) {
-                    // console.log("[Whisper] Emitting final segment:", text);
-                    // Emit as final immediately since Whisper processes distinct chunks
                     this.receiver.onFinal(text);
-                } else {
-                    // console.log("[Whisper] Skipping empty/blank segment");
                 }
             });
 
             console.log("[Whisper] Starting recording...");
             await invoke("plugin:whisper|start_recording", {
-                vadEnabled: params.whisper.vadEnabled,
-                silenceThresholdDb: parseFloat(params.whisper.silenceThresholdDb),
-                silenceDurationMs: parseInt(params.whisper.silenceDurationMs),
-                minChunkDurationMs: parseInt(params.whisper.minChunkDurationMs),
-                captureLocal: true,
+                options: {
+                    vadEnabled: params.whisper.vadEnabled,
+                    silenceThresholdDb: parseFloat(params.whisper.silenceThresholdDb),
+                    silenceDurationMs: parseInt(params.whisper.silenceDurationMs),
+                    minChunkDurationMs: parseInt(params.whisper.minChunkDurationMs),
+                    captureLocal: true,
+                    deviceName: params.whisper.device,
+                    decoding: {
+                        initialPrompt: params.whisper.initialPrompt || null,
+                        vocabulary: params.whisper.vocabulary.split(",").map(w => w.trim()).filter(w => w),
+                        carryContext: params.whisper.carryContext,
+                        beamSize: parseInt(params.whisper.beamSize) || null,
+                        nThreads: parseInt(params.whisper.threads) || null,
+                    },
+                    model: params.whisper.server ? null : params.whisper.model,
+                    server: params.whisper.server ? {
+                        endpoint: params.whisper.serverEndpoint,
+                        model: params.whisper.serverModel || null,
+                        apiKey: params.whisper.serverKey || null,
+                    } : null,
+                    interimIntervalMs: params.whisper.interim ? parseInt(params.whisper.interimIntervalMs) : null,
+                    language: params.whisper.language || "auto",
+                    task: params.whisper.translate ? "translate" : "transcribe",
+                    segmentation: params.whisper.pushToTalk ? "manual" : params.whisper.vadEnabled ? "vad" : "timer",
+                    preprocess: {
+                        highPass: params.whisper.highPass,
+                        noiseSuppression: params.whisper.noiseSuppression,
+                        noiseGate: params.whisper.noiseGate,
+                        agc: params.whisper.agc,
+                    },
+                    recorder: {
+                        saveUtterances: params.whisper.saveUtterances,
+                        replayMinutes: parseInt(params.whisper.replayMinutes) || 0,
+                    },
+                },
             });
             this.isRecording = true;
             console.log("[Whisper] Recording started - real-time transcription active");
         } catch (error) {
             console.error("[Whisper] Error starting:", error);
             this.isRecording = false;
-            if (this.unlistenPartial) {
-                this.unlistenPartial();
-                this.unlistenPartial = undefined;
+            if (this.unlistenFinal) {
+                this.unlistenFinal();
+                this.unlistenFinal = undefined;
             }
             this.receiver.onStop(String(error));
         }
@@ -81,21 +117,21 @@ export class STT_WhisperService implements ISpeechRecognitionService {
         // Set to false immediately to prevent re-entrant calls
         this.isRecording = false;
 
-        // Clean up partial result listener
-        if (this.unlistenPartial) {
-            this.unlistenPartial();
-            this.unlistenPartial = undefined;
-        }
-
         try {
             console.log("[Whisper] Stopping recording...");
+            // resolves once the last utterance went out as whisper:final, keep listening until then
             await invoke<string>("plugin:whisper|stop_recording");
-
-            // No need to emit final accumulated text as we emit segments in real-time
-
         } catch (error) {
             console.error("[Whisper] Error stopping:", error);
         } finally {
+            if (this.unlistenFinal) {
+                this.unlistenFinal();
+                this.unlistenFinal = undefined;
+            }
+            if (this.unlistenInterim) {
+                this.unlistenInterim();
+                this.unlistenInterim = undefined;
+            }
             this.receiver.onStop();
         }
     }
@@ -110,9 +146,13 @@ export class STT_WhisperService implements ISpeechRecognitionService {
             this.unlistenProgress = undefined;
         }
 
-        if (this.unlistenPartial) {
-            this.unlistenPartial();
-            this.unlistenPartial = undefined;
+        if (this.unlistenFinal) {
+            this.unlistenFinal();
+            this.unlistenFinal = undefined;
+        }
+        if (this.unlistenInterim) {
+            this.unlistenInterim();
+            this.unlistenInterim = undefined;
         }
     }
 }
@@ -527,6 +527,18 @@ export const InputNativeAudioOutput: FC<AudioOutputProps> = memo(({ label, value
         label={label} />
 });
 
+export const InputNativeAudioInput: FC<AudioOutputProps> = memo(({ label, value, onChange }) => {
+    const [devices, setDevices] = useState<string[]>([]);
+    useEffect(() => {
+        invoke<string[]>("plugin:whisper|list_input_devices").then(setDevices).catch(() => setDevices([]));
+    }, []);
+
+    return <InputSelect
+        value={value}
+        onValueChange={onChange}
+        options={[{ label: "Default", value: "default" }, ...devices.map(d => ({ label: d, value: d }))]}
+        label={label} />
+});
 
 const getMedia = async () => {
     try {
@@ -150,6 +150,10 @@ const Inspector_Settings: FC = memo(() => {
         <div className="text-xs opacity-70">
           Use <kbd className="kbd kbd-sm font-semibold text-primary">Esc</kbd> to cancel input, <kbd className="kbd kbd-sm font-semibold text-primary">Enter</kbd> to submit and <kbd className="kbd kbd-sm font-semibold text-primary">Backspace</kbd> to delete
         </div>
+
+        <Inspector.SubHeader>Push to Talk</Inspector.SubHeader>
+        <InputShortcut label="Begin Utterance" shortcut="beginUtterance" />
+        <InputShortcut label="End Utterance" shortcut="endUtterance" />
       </>}
 
 
@@ -9,7 +9,7 @@ import { useSnapshot } from "valtio";
 import { azureLanguages, deepGramLangs, nativeLangs } from "../../services/stt/stt_data";
 import ServiceButton from "../service-button";
 import Inspector from "./components";
-import { InputCheckbox, InputMappedGroupSelect, InputSelect, InputText, InputWebAudioInput } from "./components/input";
+import { InputCheckbox, InputMapObject, InputMappedGroupSelect, InputNativeAudioInput, InputSelect, InputText, InputWebAudioInput } from "./components/input";
 import NiceModal from "@ebay/nice-modal-react";
 import { useTranslation } from 'react-i18next';
 
@@ -171,6 +171,118 @@ const Speechly: FC = () => {
   </>
 }
 
+const WhisperLevel: FC = () => {
+  const [level, setLevel] = useState<{ rms_db: number, speech: boolean } | null>(null);
+
+  useEffect(() => {
+    const unlisten = listen("whisper:level", (event) => {
+      setLevel(event.payload as { rms_db: number, speech: boolean });
+    });
+    return () => {
+      unlisten.then(f => f());
+    }
+  }, []);
+
+  if (!level)
+    return null;
+  return <div className="flex items-center gap-2 text-xs">
+    <span className="w-20">{level.rms_db.toFixed(0)} dB</span>
+    <progress className={`progress w-full ${level.speech ? "progress-success" : "progress-primary"}`} value={Math.max(level.rms_db + 80, 0)} max="80"></progress>
+  </div>
+}
+
+const WhisperCalibration: FC = () => {
+  const [phase, setPhase] = useState<string | null>(null);
+  const [error, setError] = useState("");
+  const pr = useSnapshot(window.ApiServer.state.services.stt.data.whisper);
+
+  const calibrate = async () => {
+    setError("");
+    setPhase("noise");
+    const unlisten = await listen("whisper:calibration", (event) => setPhase(event.payload as string));
+    try {
+      const result = await invoke<{ silence_threshold_db: number, silence_duration_ms: number, min_chunk_duration_ms: number }>("plugin:whisper|calibrate_vad", {
+        deviceName: pr.device,
+        preprocess: { highPass: pr.highPass, noiseSuppression: pr.noiseSuppression, noiseGate: pr.noiseGate, agc: pr.agc },
+      });
+      const whisper = window.ApiServer.state.services.stt.data.whisper;
+      whisper.silenceThresholdDb = result.silence_threshold_db.toFixed(0);
+      whisper.silenceDurationMs = result.silence_duration_ms.toString();
+      whisper.minChunkDurationMs = result.min_chunk_duration_ms.toString();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      unlisten();
+      setPhase(null);
+    }
+  };
+
+  return <>
+    <button className="btn btn-sm" disabled={phase !== null} onClick={calibrate}>
+      {phase === "noise" ? "Stay quiet..." : phase === "speech" ? "Now speak normally..." : "Calibrate"}
+    </button>
+    <Inspector.Description>
+      {error || "Measures your room noise and voice, then fills in the values below."}
+    </Inspector.Description>
+  </>
+}
+
+const WhisperBenchmark: FC = () => {
+  const [running, setRunning] = useState<string | null>(null);
+  const [results, setResults] = useState<{ model: string, rtf: number, error?: string }[]>([]);
+  const [error, setError] = useState("");
+  const pr = useSnapshot(window.ApiServer.state.services.stt.data.whisper);
+
+  const run = async () => {
+    setError("");
+    setResults([]);
+    setRunning("");
+    const unlisten = await listen("whisper:benchmark_progress", (event) => setRunning((event.payload as { model: string }).model));
+    try {
+      setResults(await invoke("plugin:whisper|benchmark", {
+        decoding: { beamSize: parseInt(pr.beamSize) || null, nThreads: parseInt(pr.threads) || null },
+      }));
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      unlisten();
+      setRunning(null);
+    }
+  };
+
+  return <>
+    <button className="btn btn-sm" disabled={running !== null} onClick={run}>
+      {running !== null ? `Testing ${running}...` : "Benchmark Installed Models"}
+    </button>
+    {results.map(r => <div key={r.model} className="flex justify-between text-xs">
+      <span>{r.model}</span>
+      <span>{r.error ?? `${r.rtf.toFixed(2)}x ${r.rtf < 1 ? "(keeps up)" : "(too slow)"}`}</span>
+    </div>)}
+    <Inspector.Description>
+      {error || "Time spent per second of speech. Pick the largest model that stays well below 1x."}
+    </Inspector.Description>
+  </>
+}
+
+const WhisperReplay: FC = () => {
+  const [message, setMessage] = useState("");
+
+  const save = async () => {
+    try {
+      setMessage(`Saved to ${await invoke<string>("plugin:whisper|save_replay")}`);
+    } catch (e) {
+      setMessage(String(e));
+    }
+  };
+
+  return <>
+    <button className="btn btn-sm" onClick={save}>Save Replay</button>
+    <Inspector.Description>
+      {message || "Writes the buffered minutes to a WAV file while recording."}
+    </Inspector.Description>
+  </>
+}
+
 const Whisper: FC = () => {
   const { t } = useTranslation();
   const [downloadProgress, setDownloadProgress] = useState<{ file: string, progress: number } | null>(null);
@@ -202,6 +314,125 @@ const Whisper: FC = () => {
       </div>
     )}
 
+    <InputNativeAudioInput value={pr.device} onChange={e => up("device", e)} label="common.field_input_device" />
+
+    <InputText
+      label="Language"
+      value={pr.language}
+      onChange={e => up("language", e.target.value)}
+    />
+    <Inspector.Description>
+      Language code such as en, ja or es, or "auto" to detect it. Non-English languages need a multilingual model.
+    </Inspector.Description>
+    <InputCheckbox label="Translate to English" onChange={e => up("translate", e)} value={pr.translate} />
+
+    <Inspector.SubHeader>Server</Inspector.SubHeader>
+    <InputCheckbox label="Transcribe on a Server" onChange={e => up("server", e)} value={pr.server} />
+    <Inspector.Switchable visible={pr.server}>
+      <InputText
+        label="Endpoint"
+        value={pr.serverEndpoint}
+        onChange={e => up("serverEndpoint", e.target.value)}
+      />
+      <Inspector.Description>
+        OpenAI compatible transcription URL, such as faster-whisper-server or the whisper.cpp server. Audio is still captured and split here.
+      </Inspector.Description>
+      <InputText
+        label="Model"
+        value={pr.serverModel}
+        onChange={e => up("serverModel", e.target.value)}
+      />
+      <InputText label="API Key" type="password" value={pr.serverKey} onChange={e => up("serverKey", e.target.value)} />
+    </Inspector.Switchable>
+
+    <Inspector.SubHeader>Recognition</Inspector.SubHeader>
+    <InputText
+      label="Vocabulary"
+      value={pr.vocabulary}
+      onChange={e => up("vocabulary", e.target.value)}
+    />
+    <Inspector.Description>
+      Comma separated names, emotes and jargon Whisper should spell correctly.
+    </Inspector.Description>
+    <InputText
+      label="Initial Prompt"
+      value={pr.initialPrompt}
+      onChange={e => up("initialPrompt", e.target.value)}
+    />
+    <InputCheckbox label="Use previous sentence as context" onChange={e => up("carryContext", e)} value={pr.carryContext} />
+    <InputText
+      type="number"
+      step="1"
+      label="Beam Size"
+      value={pr.beamSize}
+      onChange={e => up("beamSize", e.target.value)}
+    />
+    <Inspector.Description>
+      1 uses greedy decoding. Larger beams are more accurate but slower. Default: 1
+    </Inspector.Description>
+    <InputText
+      type="number"
+      step="1"
+      label="Threads"
+      value={pr.threads}
+      onChange={e => up("threads", e.target.value)}
+    />
+    <InputText
+      type="number"
+      step="1"
+      label="Unload Model After (minutes)"
+      value={pr.unloadAfterMinutes}
+      onChange={e => up("unloadAfterMinutes", e.target.value)}
+    />
+    <Inspector.Description>
+      Frees the model's memory when nothing was recorded for this long. It loads again on the next start. 0 keeps it loaded.
+    </Inspector.Description>
+    <WhisperBenchmark />
+
+    <InputCheckbox label="stt.field_enable_interim_results" onChange={e => up("interim", e)} value={pr.interim} />
+    <Inspector.Switchable visible={pr.interim}>
+      <InputText
+        type="number"
+        step="100"
+        label="Interim Update Interval (ms)"
+        value={pr.interimIntervalMs}
+        onChange={e => up("interimIntervalMs", e.target.value)}
+      />
+      <Inspector.Description>
+        How often the current sentence is re-transcribed while you speak. Default: 500ms
+      </Inspector.Description>
+    </Inspector.Switchable>
+
+    <Inspector.SubHeader>Audio Processing</Inspector.SubHeader>
+    <InputCheckbox label="High-pass Filter" onChange={e => up("highPass", e)} value={pr.highPass} />
+    <InputCheckbox label="Noise Suppression" onChange={e => up("noiseSuppression", e)} value={pr.noiseSuppression} />
+    <InputCheckbox label="Noise Gate" onChange={e => up("noiseGate", e)} value={pr.noiseGate} />
+    <InputCheckbox label="Automatic Gain" onChange={e => up("agc", e)} value={pr.agc} />
+    <Inspector.Description>
+      Cleans up the microphone before VAD and recognition. Try noise suppression for fans and automatic gain for quiet mics.
+    </Inspector.Description>
+
+    <Inspector.SubHeader>Recorder</Inspector.SubHeader>
+    <InputCheckbox label="Save Each Utterance" onChange={e => up("saveUtterances", e)} value={pr.saveUtterances} />
+    <Inspector.Description>
+      Keeps the audio Whisper heard and its transcript, in the recordings folder of the app data. Useful for building a correction list.
+    </Inspector.Description>
+    <InputText
+      type="number"
+      step="1"
+      label="Replay Buffer (minutes)"
+      value={pr.replayMinutes}
+      onChange={e => up("replayMinutes", e.target.value)}
+    />
+    <Inspector.Switchable visible={parseInt(pr.replayMinutes) > 0}>
+      <WhisperReplay />
+    </Inspector.Switchable>
+
+    <Inspector.SubHeader>Push to Talk</Inspector.SubHeader>
+    <InputCheckbox label="Enable Push to Talk" onChange={e => up("pushToTalk", e)} value={pr.pushToTalk} />
+    <Inspector.Description>
+      Only transcribes what you say between the begin and end utterance shortcuts in Settings. Use it where noise keeps VAD from working.
+    </Inspector.Description>
 
     <Inspector.SubHeader>Voice Activity Detection</Inspector.SubHeader>
     <InputCheckbox
@@ -214,6 +445,9 @@ const Whisper: FC = () => {
     </Inspector.Description>
 
     <Inspector.Switchable visible={pr.vadEnabled}>
+      <WhisperLevel />
+      <WhisperCalibration />
+
       <InputText
         type="number"
         step="1"
@@ -244,7 +478,7 @@ const Whisper: FC = () => {
         onChange={e => up("minChunkDurationMs", e.target.value)}
       />
       <Inspector.Description>
-        Minimum audio length before VAD can trigger (500-2000ms). Default: 1000ms
+        Minimum voiced time for an utterance to count as speech (100-2000ms), shorter bursts are dropped as clicks. Default: 250ms
       </Inspector.Description>
     </Inspector.Switchable>
   </>
@@ -317,6 +551,23 @@ const Inspector_STT: FC = () => {
         <span className="link link-accent link-hover font-semibold flex items-center gap-2 text-sm justify-end" onClick={handleShowReplacements}><RiCharacterRecognitionFill />{t('common.btn_edit_replacements')}</span>
       </div>
 
+      <Inspector.SubHeader>Voice Commands</Inspector.SubHeader>
+      <InputMapObject keyPlaceholder="Phrase" valuePlaceholder="Command" addLabel="Add command" value={{ ...data.data.voiceCommands }} onChange={e => up("voiceCommands", e)} label="" />
+      <Inspector.Description>
+        Final results close enough to a phrase send a voice_command event with its command.
+      </Inspector.Description>
+      <InputText
+        type="number"
+        step="5"
+        label="Match Threshold (%)"
+        value={data.data.voiceCommandsThreshold}
+        onChange={e => up("voiceCommandsThreshold", e.target.value)}
+      />
+      <Inspector.Description>
+        How close the words have to be to the phrase. Lower = more forgiving, more false triggers. Default: 80%
+      </Inspector.Description>
+      <InputCheckbox label="Hide Commands from Captions" onChange={e => up("voiceCommandsSuppress", e)} value={data.data.voiceCommandsSuppress} />
+
     </Inspector.Content>
   </Inspector.Body>
 }